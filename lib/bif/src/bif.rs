@@ -0,0 +1,172 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use media_time::MediaTime;
+
+const MAGIC: [u8; 8] = [0x89, 0x42, 0x49, 0x46, 0x0d, 0x0a, 0x1a, 0x0a];
+const VERSION: u32 = 0;
+/// Size of the header, the index table starts right after it
+const HEADER_SIZE: usize = 64;
+/// Timestamp of the index entry terminating the table
+const END_OF_INDEX: u32 = u32::MAX;
+
+/// Roku Base Index Frames archive, a list of JPEG images with their timestamps
+pub struct BifFile {
+    frames: Vec<(MediaTime, Vec<u8>)>,
+}
+
+#[derive(Error, Debug)]
+pub enum BifError {
+    #[error("Error saving file {0}")]
+    IoError(PathBuf, #[source] std::io::Error),
+    #[error("Error loading file {0}")]
+    LoadError(PathBuf, #[source] std::io::Error),
+    #[error("Invalid file {0}: {1}")]
+    InvalidFile(PathBuf, String),
+    #[error("Timestamp {0} can not be represented in the index")]
+    TimestampOutOfRange(MediaTime),
+    #[error("Offset {0} exceeds the 4 GiB addressable by the index")]
+    OffsetOutOfRange(usize),
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| format!("truncated at offset {}", offset))
+}
+
+impl Default for BifFile {
+    fn default() -> Self {
+        BifFile::new()
+    }
+}
+
+impl BifFile {
+    pub fn new() -> BifFile {
+        BifFile { frames: Vec::new() }
+    }
+
+    /// Adds a JPEG image, frames have to be added in presentation order
+    pub fn add(&mut self, timestamp: MediaTime, jpeg: Vec<u8>) {
+        self.frames.push((timestamp, jpeg));
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn frames(&self) -> &[(MediaTime, Vec<u8>)] {
+        &self.frames
+    }
+
+    /// Keeps only the first `len` frames
+    pub fn truncate(&mut self, len: usize) {
+        self.frames.truncate(len);
+    }
+
+    /// Timestamps are stored in whole seconds where possible, which is what Roku's own tooling
+    /// writes, and in milliseconds otherwise
+    fn multiplier(&self) -> u32 {
+        if self.frames.iter().all(|(timestamp, _)| timestamp.milliseconds() % 1000 == 0) {
+            1000
+        } else {
+            1
+        }
+    }
+
+    pub(crate) fn parse(data: &[u8]) -> Result<BifFile, String> {
+        if data.get(..MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err(String::from("missing BIF signature"));
+        }
+        let version = read_u32(data, 8)?;
+        if version != VERSION {
+            return Err(format!("unsupported version {}", version));
+        }
+        let count = read_u32(data, 12)? as usize;
+        let multiplier = match read_u32(data, 16)? {
+            0 => 1000,
+            multiplier => multiplier,
+        };
+
+        let entry = |index: usize| -> Result<(u32, usize), String> {
+            let offset = HEADER_SIZE + index * 8;
+            Ok((read_u32(data, offset)?, read_u32(data, offset + 4)? as usize))
+        };
+        let mut file = BifFile::new();
+        for index in 0..count {
+            let (timestamp, start) = entry(index)?;
+            let (_, end) = entry(index + 1)?;
+            let jpeg = data
+                .get(start..end)
+                .ok_or_else(|| format!("frame {} out of bounds", index))?;
+            file.add(
+                MediaTime::from_millis(timestamp as i64 * multiplier as i64),
+                jpeg.to_vec(),
+            );
+        }
+        Ok(file)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<BifFile, BifError> {
+        let data = std::fs::read(&path)
+            .map_err(|err| BifError::LoadError(path.as_ref().to_path_buf(), err))?;
+        BifFile::parse(&data).map_err(|err| BifError::InvalidFile(path.as_ref().to_path_buf(), err))
+    }
+
+    /// Index entries of all frames and the terminating entry, as pairs of timestamp and offset
+    pub(crate) fn index(&self) -> Result<Vec<(u32, u32)>, BifError> {
+        let multiplier = self.multiplier() as i128;
+        let mut offset = HEADER_SIZE + (self.frames.len() + 1) * 8;
+        let mut index = Vec::with_capacity(self.frames.len() + 1);
+        let index_offset =
+            |offset: usize| u32::try_from(offset).map_err(|_| BifError::OffsetOutOfRange(offset));
+        for (timestamp, jpeg) in &self.frames {
+            let value = u32::try_from(timestamp.milliseconds() / multiplier)
+                .ok()
+                .filter(|&value| value != END_OF_INDEX)
+                .ok_or(BifError::TimestampOutOfRange(*timestamp))?;
+            index.push((value, index_offset(offset)?));
+            offset += jpeg.len();
+        }
+        index.push((END_OF_INDEX, index_offset(offset)?));
+        Ok(index)
+    }
+
+    pub(crate) fn write(
+        &self,
+        file: &mut impl Write,
+        index: &[(u32, u32)],
+    ) -> Result<(), std::io::Error> {
+        let mut header = [0u8; HEADER_SIZE];
+        header[..8].copy_from_slice(&MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        header[12..16].copy_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        header[16..20].copy_from_slice(&self.multiplier().to_le_bytes());
+        file.write_all(&header)?;
+
+        for (timestamp, offset) in index {
+            file.write_all(&timestamp.to_le_bytes())?;
+            file.write_all(&offset.to_le_bytes())?;
+        }
+        for (_, jpeg) in &self.frames {
+            file.write_all(jpeg)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BifError> {
+        let index = self.index()?;
+        File::create(&path)
+            .and_then(|file| self.write(&mut BufWriter::new(file), &index))
+            .map_err(|err| BifError::IoError(path.as_ref().to_path_buf(), err))
+    }
+}
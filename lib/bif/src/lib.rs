@@ -0,0 +1,6 @@
+#[cfg(test)]
+mod tests;
+
+mod bif;
+
+pub use bif::BifFile;
@@ -0,0 +1,59 @@
+use media_time::MediaTime;
+
+use crate::BifFile;
+
+fn write(file: &BifFile) -> Vec<u8> {
+    let mut data = Vec::new();
+    file.write(&mut data, &file.index().unwrap()).unwrap();
+    data
+}
+
+#[test]
+fn write_puts_header_and_index() {
+    let mut file = BifFile::new();
+    file.add(MediaTime::from_seconds(0), vec![0xff, 0xd8, 0xff, 0xd9]);
+    file.add(MediaTime::from_seconds(10), vec![0xff, 0xd8, 0x00, 0xff, 0xd9]);
+
+    let data = write(&file);
+    assert_eq!(&data[..8], &[0x89, 0x42, 0x49, 0x46, 0x0d, 0x0a, 0x1a, 0x0a]);
+    assert_eq!(&data[8..12], &0u32.to_le_bytes());
+    assert_eq!(&data[12..16], &2u32.to_le_bytes());
+    assert_eq!(&data[16..20], &1000u32.to_le_bytes());
+    assert!(data[20..64].iter().all(|&byte| byte == 0));
+    assert_eq!(
+        &data[64..88],
+        &[
+            0, 0, 0, 0, 88, 0, 0, 0, //
+            10, 0, 0, 0, 92, 0, 0, 0, //
+            0xff, 0xff, 0xff, 0xff, 97, 0, 0, 0,
+        ]
+    );
+    assert_eq!(&data[88..], &[0xff, 0xd8, 0xff, 0xd9, 0xff, 0xd8, 0x00, 0xff, 0xd9]);
+}
+
+#[test]
+fn write_uses_milliseconds_for_fractional_timestamps() {
+    let mut file = BifFile::new();
+    file.add(MediaTime::from_millis(500), vec![0xff, 0xd8, 0xff, 0xd9]);
+
+    let data = write(&file);
+    assert_eq!(&data[16..20], &1u32.to_le_bytes());
+    assert_eq!(&data[64..68], &500u32.to_le_bytes());
+}
+
+#[test]
+fn parse_reads_written_file() {
+    let mut file = BifFile::new();
+    file.add(MediaTime::from_millis(0), vec![1, 2, 3]);
+    file.add(MediaTime::from_millis(2500), vec![4, 5]);
+
+    let loaded = BifFile::parse(&write(&file)).unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.frames()[1].0, MediaTime::from_millis(2500));
+    assert_eq!(loaded.frames()[1].1, vec![4, 5]);
+}
+
+#[test]
+fn parse_rejects_missing_signature() {
+    assert!(BifFile::parse(&[0u8; 72]).is_err());
+}
@@ -9,6 +9,8 @@ use thiserror::Error;
 
 use crate::enums::*;
 use crate::err::AVError;
+use crate::err_av::AvInternalError;
+use crate::err_ffi::AvFfiError;
 
 #[derive(Error, Debug)]
 pub enum AVAllocError {
@@ -35,6 +37,13 @@ fn native_string(ptr: *const std::os::raw::c_char) -> Result<String, StringError
     }
 }
 
+fn rational(fraction: &Fraction) -> ffi::AVRational {
+    ffi::AVRational {
+        num: fraction.numer().copied().unwrap_or(0) as std::os::raw::c_int,
+        den: fraction.denom().copied().unwrap_or(1) as std::os::raw::c_int,
+    }
+}
+
 pub struct AVFormatContext {
     base: *mut ffi::AVFormatContext,
 }
@@ -49,6 +58,10 @@ pub enum AVFormatContextError {
     PathContainsNull(PathBuf, #[source] std::ffi::NulError),
     #[error("Opening media file {0} failed")]
     OpenInputFailed(PathBuf, #[source] AVError),
+    #[error("Seeking to {0} failed")]
+    SeekFailed(media_time::MediaTime, #[source] AVError),
+    #[error("Resetting end of file state failed")]
+    ClearEofFailed(#[source] AVError),
 }
 
 impl AVFormatContext {
@@ -100,11 +113,45 @@ impl AVFormatContext {
             .map_err(|err| AVFrameError::DecodingFailed(packet.stream_index(), packet.pts(), err))
     }
 
-    pub fn duration(&self) -> Result<media_time::MediaTime, MediaTimeError> {
-        media_time::MediaTime::from_rational(
-            unsafe { (*self.base).duration },
-            &Fraction::new(1 as u64, ffi::AV_TIME_BASE as u64),
-        )
+    /// Resets the end of file state of the input, so reading continues on a growing file. Seeking
+    /// the I/O context to its current position clears the state.
+    pub fn clear_eof(&mut self) -> Result<(), AVFormatContextError> {
+        let pb = unsafe { (*self.base).pb };
+        if pb.is_null() {
+            return Ok(());
+        }
+        let seek = |offset: i64, whence: u32| unsafe {
+            ffi::avio_seek(pb, offset, whence as std::os::raw::c_int)
+        };
+        let result = match seek(0, ffi::SEEK_CUR) {
+            error if error < 0 => error,
+            position => seek(position, ffi::SEEK_SET),
+        };
+        AVError::from_errno(result.min(0) as i32).map_err(AVFormatContextError::ClearEofFailed)
+    }
+
+    /// Returns None if the duration is unknown, e.g. for raw streams, pipes or live inputs
+    pub fn duration(&self) -> Result<Option<media_time::MediaTime>, MediaTimeError> {
+        match unsafe { (*self.base).duration } {
+            ffi::AV_NOPTS_VALUE => Ok(None),
+            duration => media_time::MediaTime::from_rational(
+                duration,
+                &Fraction::new(1 as u64, ffi::AV_TIME_BASE as u64),
+            )
+            .map(Some),
+        }
+    }
+
+    /// Seeks all streams to the last keyframe at or before the given timestamp
+    pub fn seek(&mut self, timestamp: media_time::MediaTime) -> Result<(), AVFormatContextError> {
+        AVError::from_errno(unsafe {
+            ffi::av_seek_frame(
+                self.base,
+                -1,
+                (timestamp.microseconds() * ffi::AV_TIME_BASE as i128 / 1_000_000) as i64,
+                ffi::AVSEEK_FLAG_BACKWARD as std::os::raw::c_int,
+            )
+        }).map_err(|err| AVFormatContextError::SeekFailed(timestamp, err))
     }
 }
 
@@ -114,6 +161,145 @@ impl Drop for AVFormatContext {
     }
 }
 
+/// Muxer writing encoded packets into a file, with the container guessed from its extension
+pub struct AVOutputContext {
+    base: *mut ffi::AVFormatContext,
+    path: PathBuf,
+}
+
+#[derive(Error, Debug)]
+pub enum AVOutputContextError {
+    #[error(transparent)]
+    AllocFailed(#[from] AVAllocError),
+    #[error("Path {0} is invalid")]
+    PathInvalid(PathBuf),
+    #[error("Path {0} contains null byte")]
+    PathContainsNull(PathBuf, #[source] std::ffi::NulError),
+    #[error("No output format found for {0}")]
+    FormatNotFound(PathBuf, #[source] AVError),
+    #[error("Copying codec parameters to output stream failed")]
+    CodecParametersFailed(#[source] AVError),
+    #[error("Opening output file {0} failed")]
+    OpenOutputFailed(PathBuf, #[source] AVError),
+    #[error("Writing header of {0} failed")]
+    WriteHeaderFailed(PathBuf, #[source] AVError),
+    #[error("Stream {1} of {0} does not exist")]
+    StreamNotFound(PathBuf, i32),
+    #[error("Writing packet to {0} failed")]
+    WritePacketFailed(PathBuf, #[source] AVError),
+    #[error("Writing trailer of {0} failed")]
+    WriteTrailerFailed(PathBuf, #[source] AVError),
+}
+
+impl AVOutputContext {
+    pub fn new(path: &Path) -> Result<Self, AVOutputContextError> {
+        let pathname = path
+            .to_str()
+            .ok_or(AVOutputContextError::PathInvalid(path.to_path_buf()))?;
+        let pathname = std::ffi::CString::new(pathname)
+            .map_err(|err| AVOutputContextError::PathContainsNull(path.to_path_buf(), err))?;
+
+        let mut base: *mut ffi::AVFormatContext = std::ptr::null_mut();
+        AVError::from_errno(unsafe {
+            ffi::avformat_alloc_output_context2(
+                &mut base,
+                std::ptr::null(),
+                std::ptr::null(),
+                pathname.as_ptr(),
+            )
+        }.min(0))
+            .map_err(|err| AVOutputContextError::FormatNotFound(path.to_path_buf(), err))?;
+        if base.is_null() {
+            return Err(AVAllocError::AllocFailed("AVFormatContext".to_string()).into());
+        }
+
+        Ok(AVOutputContext { base, path: path.to_path_buf() })
+    }
+
+    fn flags(&self) -> u32 {
+        unsafe { (*(*self.base).oformat).flags as u32 }
+    }
+
+    /// Whether encoders have to put their extradata into the container header, see
+    /// [`AVCodecContext::set_global_header`]
+    pub fn needs_global_header(&self) -> bool {
+        self.flags() & ffi::AVFMT_GLOBALHEADER != 0
+    }
+
+    /// Adds a stream for the packets of an opened encoder and returns its index
+    pub fn add_stream(&mut self, encoder: &AVCodecContext) -> Result<i32, AVOutputContextError> {
+        let stream = unsafe { ffi::avformat_new_stream(self.base, std::ptr::null()) };
+        if stream.is_null() {
+            return Err(AVAllocError::AllocFailed("AVStream".to_string()).into());
+        }
+        unsafe {
+            AVError::from_errno(
+                ffi::avcodec_parameters_from_context((*stream).codecpar, encoder.base).min(0),
+            )
+                .map_err(AVOutputContextError::CodecParametersFailed)?;
+            // Only a hint, the muxer may choose a different time base when writing the header
+            (*stream).time_base = encoder.as_ref().time_base;
+            Ok((*stream).index)
+        }
+    }
+
+    /// Opens the output file and writes the container header, after all streams were added
+    pub fn write_header(&mut self) -> Result<(), AVOutputContextError> {
+        if self.flags() & ffi::AVFMT_NOFILE == 0 {
+            let pathname = std::ffi::CString::new(self.path.to_string_lossy().as_bytes())
+                .map_err(|err| AVOutputContextError::PathContainsNull(self.path.clone(), err))?;
+            AVError::from_errno(unsafe {
+                ffi::avio_open(
+                    &mut (*self.base).pb,
+                    pathname.as_ptr(),
+                    ffi::AVIO_FLAG_WRITE as std::os::raw::c_int,
+                )
+            }.min(0))
+                .map_err(|err| AVOutputContextError::OpenOutputFailed(self.path.clone(), err))?;
+        }
+
+        AVError::from_errno(
+            unsafe { ffi::avformat_write_header(self.base, std::ptr::null_mut()) }.min(0),
+        )
+            .map_err(|err| AVOutputContextError::WriteHeaderFailed(self.path.clone(), err))
+    }
+
+    /// Writes a packet to the stream, converting its timestamps from the encoder's time base
+    pub fn write_packet(
+        &mut self,
+        packet: &mut AVPacket,
+        stream_index: i32,
+        time_base: &Fraction,
+    ) -> Result<(), AVOutputContextError> {
+        if stream_index < 0 || stream_index as u32 >= unsafe { (*self.base).nb_streams } {
+            return Err(AVOutputContextError::StreamNotFound(self.path.clone(), stream_index));
+        }
+        unsafe {
+            let stream = *(*self.base).streams.add(stream_index as usize);
+            (*packet.base).stream_index = stream_index;
+            ffi::av_packet_rescale_ts(packet.base, rational(time_base), (*stream).time_base);
+        }
+        AVError::from_errno(unsafe { ffi::av_interleaved_write_frame(self.base, packet.base) })
+            .map_err(|err| AVOutputContextError::WritePacketFailed(self.path.clone(), err))
+    }
+
+    pub fn write_trailer(&mut self) -> Result<(), AVOutputContextError> {
+        AVError::from_errno(unsafe { ffi::av_write_trailer(self.base) })
+            .map_err(|err| AVOutputContextError::WriteTrailerFailed(self.path.clone(), err))
+    }
+}
+
+impl Drop for AVOutputContext {
+    fn drop(&mut self) {
+        unsafe {
+            if self.flags() & ffi::AVFMT_NOFILE == 0 {
+                ffi::avio_closep(&mut (*self.base).pb);
+            }
+            ffi::avformat_free_context(self.base)
+        }
+    }
+}
+
 pub struct AVInputFormat<'a> {
     base: &'a ffi::AVInputFormat,
 }
@@ -256,6 +442,18 @@ pub enum AVFrameError {
     AllocFailed(#[from] AVAllocError),
     #[error("Decoding a frame from packet of stream {0} at timestamp {1} failed")]
     DecodingFailed(i32, i64, #[source] AVError),
+    #[error("Cropping frame to {2}x{3} at {0},{1} failed")]
+    CroppingFailed(i32, i32, i32, i32, #[source] AVError),
+}
+
+impl AVFrameError {
+    /// Whether the error only signals that the end of the input was reached
+    pub fn is_end_of_file(&self) -> bool {
+        matches!(
+            self,
+            AVFrameError::DecodingFailed(_, _, AVError::Internal(AvInternalError::EndOfFile))
+        )
+    }
 }
 
 impl AVFrame {
@@ -310,6 +508,24 @@ impl AVFrame {
         AVPixelFormat::from_i32(self.as_ref().format).unwrap_or(AVPixelFormat::NONE)
     }
 
+    pub fn color_primaries(&self) -> AVColorPrimaries {
+        AVColorPrimaries::from_u32(self.as_ref().color_primaries)
+            .unwrap_or(AVColorPrimaries::Unspecified)
+    }
+
+    pub fn color_trc(&self) -> AVColorTransferCharacteristic {
+        AVColorTransferCharacteristic::from_u32(self.as_ref().color_trc)
+            .unwrap_or(AVColorTransferCharacteristic::Unspecified)
+    }
+
+    pub fn colorspace(&self) -> AVColorSpace {
+        AVColorSpace::from_u32(self.as_ref().colorspace).unwrap_or(AVColorSpace::Unspecified)
+    }
+
+    pub fn color_range(&self) -> AVColorRange {
+        AVColorRange::from_u32(self.as_ref().color_range).unwrap_or(AVColorRange::Unspecified)
+    }
+
     pub fn size(&self) -> usize {
         unsafe {
             ffi::av_image_get_buffer_size(
@@ -329,6 +545,72 @@ impl AVFrame {
         self.as_ref().pts
     }
 
+    /// Sets the pts in the time base of the encoder the frame is sent to
+    pub fn set_pts(&mut self, pts: i64) {
+        self.as_mut().pts = pts
+    }
+
+    /// Returns the pts, falling back to the timestamp guessed by the decoder for streams without
+    /// reliable pts, or None if neither is known
+    pub fn presentation_timestamp(&self) -> Option<i64> {
+        [self.as_ref().pts, self.as_ref().best_effort_timestamp]
+            .into_iter()
+            .find(|&timestamp| timestamp != ffi::AV_NOPTS_VALUE)
+    }
+
+    pub fn interlaced_frame(&self) -> bool {
+        self.as_ref().flags as u32 & ffi::AV_FRAME_FLAG_INTERLACED != 0
+    }
+
+    pub fn top_field_first(&self) -> bool {
+        self.as_ref().flags as u32 & ffi::AV_FRAME_FLAG_TOP_FIELD_FIRST != 0
+    }
+
+    /// Restricts the frame to a single field by doubling the line stride and halving its height.
+    /// Only the frame's plane pointers are modified, the underlying buffer is left untouched.
+    pub fn select_field(&mut self, bottom: bool) {
+        let descriptor = unsafe { ffi::av_pix_fmt_desc_get(self.format() as ffi::AVPixelFormat) };
+        let chroma_shift = unsafe { descriptor.as_ref() }
+            .map(|descriptor| descriptor.log2_chroma_h as i32)
+            .unwrap_or(0);
+        // The top field gets the extra line of odd heights
+        let field_lines = |lines: i32| if bottom { lines / 2 } else { (lines + 1) / 2 };
+
+        let frame = self.as_mut();
+        for plane in 0..frame.data.len() {
+            if frame.data[plane].is_null() {
+                break;
+            }
+            if bottom {
+                frame.data[plane] =
+                    unsafe { frame.data[plane].offset(frame.linesize[plane] as isize) };
+            }
+            frame.linesize[plane] *= 2;
+        }
+        // Consumers derive the chroma line count from the height by rounding up, so the height
+        // is limited to what the field's share of the chroma lines covers
+        let chroma_lines = field_lines(-((-frame.height) >> chroma_shift));
+        frame.height = field_lines(frame.height).min(chroma_lines << chroma_shift);
+    }
+
+    /// Restricts the frame to the given rectangle by adjusting its plane pointers and size
+    pub fn crop(&mut self, x: i32, y: i32, width: i32, height: i32) -> Result<(), AVFrameError> {
+        let frame_width = self.width();
+        let frame_height = self.height();
+        let frame = self.as_mut();
+        frame.crop_left = x.max(0) as usize;
+        frame.crop_top = y.max(0) as usize;
+        frame.crop_right = (frame_width - x - width).max(0) as usize;
+        frame.crop_bottom = (frame_height - y - height).max(0) as usize;
+
+        AVError::from_errno(unsafe {
+            ffi::av_frame_apply_cropping(
+                self.base,
+                ffi::AV_FRAME_CROP_UNALIGNED as std::os::raw::c_int,
+            )
+        }).map_err(|err| AVFrameError::CroppingFailed(x, y, width, height, err))
+    }
+
     pub fn coded_picture_number(&self) -> i32 {
         self.as_ref().coded_picture_number
     }
@@ -391,8 +673,20 @@ impl<'a> AVStream<'a> {
         media_time::MediaTime::from_rational(timestamp, &self.time_base())
     }
 
-    pub fn duration(&self) -> Result<media_time::MediaTime, MediaTimeError> {
-        self.timestamp(self.base.duration)
+    /// Returns None if the duration is unknown
+    pub fn duration(&self) -> Result<Option<media_time::MediaTime>, MediaTimeError> {
+        match self.base.duration {
+            ffi::AV_NOPTS_VALUE => Ok(None),
+            duration => self.timestamp(duration).map(Some),
+        }
+    }
+
+    /// Returns None if the start time is unknown
+    pub fn start_time(&self) -> Result<Option<media_time::MediaTime>, MediaTimeError> {
+        match self.base.start_time {
+            ffi::AV_NOPTS_VALUE => Ok(None),
+            start_time => self.timestamp(start_time).map(Some),
+        }
     }
 
     pub fn frame_count(&self) -> i64 {
@@ -454,6 +748,14 @@ impl<'a> AVCodecParameters<'a> {
         self.base.bit_rate
     }
 
+    pub fn width(&self) -> i32 {
+        self.base.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.base.height
+    }
+
     pub fn find_decoder(&self) -> Result<AVCodec, AVCodecError> {
         Ok(AVCodec::new(
             unsafe { ffi::avcodec_find_decoder(self.base.codec_id).as_ref() }
@@ -472,6 +774,8 @@ pub struct AVCodec<'a> {
 pub enum AVCodecError {
     #[error("AVCodec not valid")]
     Invalid,
+    #[error("Encoder {0} not found")]
+    EncoderNotFound(String),
     #[error("Field {0} is invalid")]
     FieldInaccessible(String, #[source] StringError),
 }
@@ -484,6 +788,18 @@ impl<'a> AVCodec<'a> {
         };
     }
 
+    /// Looks up an encoder by name, like "libx264"
+    pub fn find_encoder(name: &str) -> Result<AVCodec<'static>, AVCodecError> {
+        let c_name = std::ffi::CString::new(name)
+            .map_err(|_| AVCodecError::EncoderNotFound(name.to_string()))?;
+        let base = unsafe { ffi::avcodec_find_encoder_by_name(c_name.as_ptr()).as_ref() }
+            .ok_or_else(|| AVCodecError::EncoderNotFound(name.to_string()))?;
+        Ok(AVCodec {
+            base,
+            phantom: PhantomData,
+        })
+    }
+
     pub fn name(&self) -> Result<String, AVCodecError> {
         native_string(self.base.name)
             .map_err(|err| AVCodecError::FieldInaccessible("name".to_string(), err))
@@ -504,6 +820,14 @@ pub enum AVCodecContextError {
     PacketError(#[source] AVError),
     #[error("Error decoding frame")]
     FrameError(#[source] AVError),
+    #[error("Error encoding frame")]
+    EncodingFailed(#[source] AVError),
+    #[error("Opening codec failed")]
+    OpenFailed(#[source] AVError),
+    #[error("Option {0} contains null byte")]
+    OptionContainsNull(String, #[source] std::ffi::NulError),
+    #[error("Setting option {0} to {1} failed")]
+    OptionFailed(String, String, #[source] AVError),
 }
 
 impl AVCodecContext {
@@ -567,11 +891,94 @@ impl AVCodecContext {
         }
     }
 
+    /// Drops all buffered frames, required after seeking
+    pub fn flush(&mut self) {
+        unsafe { ffi::avcodec_flush_buffers(self.base) }
+    }
+
     pub fn open(&mut self, codec: &AVCodec) {
         unsafe {
             ffi::avcodec_open2(self.base, codec.base, std::ptr::null_mut());
         }
     }
+
+    /// Configures an encoder for frames of the given size and format, with timestamps in the
+    /// given time base
+    pub fn set_encoder_parameters(
+        &mut self,
+        width: i32,
+        height: i32,
+        format: AVPixelFormat,
+        time_base: &Fraction,
+    ) {
+        let context = self.as_mut();
+        context.width = width;
+        context.height = height;
+        context.pix_fmt = format as ffi::AVPixelFormat;
+        context.time_base = rational(time_base);
+    }
+
+    pub fn time_base(&self) -> Fraction {
+        let time_base = self.as_ref().time_base;
+        Fraction::new(time_base.num as u32, time_base.den as u32)
+    }
+
+    pub fn set_bit_rate(&mut self, bit_rate: i64) {
+        self.as_mut().bit_rate = bit_rate
+    }
+
+    pub fn set_gop_size(&mut self, gop_size: i32) {
+        self.as_mut().gop_size = gop_size
+    }
+
+    /// Puts the codec extradata into the container header instead of every keyframe, required
+    /// by containers like MP4
+    pub fn set_global_header(&mut self) {
+        self.as_mut().flags |= ffi::AV_CODEC_FLAG_GLOBAL_HEADER as std::os::raw::c_int
+    }
+
+    /// Sets an option of the codec or its private options, like "crf" or "preset" of libx264
+    pub fn set_option(&mut self, key: &str, value: &str) -> Result<(), AVCodecContextError> {
+        let c_key = std::ffi::CString::new(key)
+            .map_err(|err| AVCodecContextError::OptionContainsNull(key.to_string(), err))?;
+        let c_value = std::ffi::CString::new(value)
+            .map_err(|err| AVCodecContextError::OptionContainsNull(key.to_string(), err))?;
+        AVError::from_errno(unsafe {
+            ffi::av_opt_set(
+                self.base as *mut std::os::raw::c_void,
+                c_key.as_ptr(),
+                c_value.as_ptr(),
+                ffi::AV_OPT_SEARCH_CHILDREN as std::os::raw::c_int,
+            )
+        })
+        .map_err(|err| AVCodecContextError::OptionFailed(key.to_string(), value.to_string(), err))
+    }
+
+    /// Like [`AVCodecContext::open`], but reports failures, which are common for encoders
+    /// rejecting their configuration
+    pub fn open_encoder(&mut self, codec: &AVCodec) -> Result<(), AVCodecContextError> {
+        AVError::from_errno(unsafe {
+            ffi::avcodec_open2(self.base, codec.base, std::ptr::null_mut())
+        })
+        .map_err(AVCodecContextError::OpenFailed)
+    }
+
+    /// Sends a frame to the encoder, or None to flush it at the end of the stream
+    pub fn in_frame(&mut self, frame: Option<&AVFrame>) -> Result<(), AVCodecContextError> {
+        let frame = frame.map_or(std::ptr::null(), |frame| frame.base as *const ffi::AVFrame);
+        AVError::from_errno(unsafe { ffi::avcodec_send_frame(self.base, frame) })
+            .map_err(AVCodecContextError::EncodingFailed)
+    }
+
+    /// Returns false if the encoder needs more input or has been flushed completely
+    pub fn out_packet(&mut self, packet: &mut AVPacket) -> Result<bool, AVCodecContextError> {
+        match AVError::from_errno(unsafe { ffi::avcodec_receive_packet(self.base, packet.base) }) {
+            Ok(()) => Ok(true),
+            Err(AVError::System(AvFfiError::TryAgain))
+            | Err(AVError::Internal(AvInternalError::EndOfFile)) => Ok(false),
+            Err(err) => Err(AVCodecContextError::EncodingFailed(err)),
+        }
+    }
 }
 
 impl Drop for AVCodecContext {
@@ -588,6 +995,10 @@ pub struct SwsContext {
 pub enum SwsContextError {
     #[error(transparent)]
     AllocFailed(#[from] AVAllocError),
+    #[error("Reading colorspace details failed")]
+    ColorspaceDetailsUnavailable,
+    #[error("Setting colorspace details to {0:?} with range {1:?} failed")]
+    ColorspaceDetailsFailed(AVColorSpace, AVColorRange),
 }
 
 impl SwsContext {
@@ -628,6 +1039,59 @@ impl SwsContext {
         }
     }
 
+    pub fn set_colorspace_details(
+        &mut self,
+        source_space: AVColorSpace,
+        source_range: AVColorRange,
+    ) -> Result<(), SwsContextError> {
+        let mut inv_table: *mut std::os::raw::c_int = std::ptr::null_mut();
+        let mut src_range: std::os::raw::c_int = 0;
+        let mut table: *mut std::os::raw::c_int = std::ptr::null_mut();
+        let mut dst_range: std::os::raw::c_int = 0;
+        let mut brightness: std::os::raw::c_int = 0;
+        let mut contrast: std::os::raw::c_int = 0;
+        let mut saturation: std::os::raw::c_int = 0;
+
+        if unsafe {
+            ffi::sws_getColorspaceDetails(
+                self.base,
+                &mut inv_table,
+                &mut src_range,
+                &mut table,
+                &mut dst_range,
+                &mut brightness,
+                &mut contrast,
+                &mut saturation,
+            )
+        } < 0 {
+            return Err(SwsContextError::ColorspaceDetailsUnavailable);
+        }
+
+        // Unspecified ranges keep the default swscale derived from the pixel format
+        match source_range {
+            AVColorRange::MPEG => src_range = 0,
+            AVColorRange::JPEG => src_range = 1,
+            AVColorRange::Unspecified => {}
+        }
+
+        if unsafe {
+            ffi::sws_setColorspaceDetails(
+                self.base,
+                ffi::sws_getCoefficients(source_space as std::os::raw::c_int),
+                src_range,
+                table,
+                dst_range,
+                brightness,
+                contrast,
+                saturation,
+            )
+        } < 0 {
+            return Err(SwsContextError::ColorspaceDetailsFailed(source_space, source_range));
+        }
+
+        Ok(())
+    }
+
     pub fn scale(&self, source: &AVFrame, target: &mut AVFrame) -> i32 {
         self.scale_slice(source, target, 0, source.height())
     }
@@ -658,3 +1122,226 @@ impl Drop for SwsContext {
         unsafe { ffi::sws_freeContext(self.base) }
     }
 }
+
+pub struct AVFilterGraph {
+    base: *mut ffi::AVFilterGraph,
+    source: *mut ffi::AVFilterContext,
+    sink: *mut ffi::AVFilterContext,
+    width: i32,
+    height: i32,
+    format: AVPixelFormat,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AVFilterStatus {
+    /// A filtered frame was returned
+    Frame,
+    /// The graph needs more input before it can return the next frame
+    NeedsInput,
+    /// The graph has ended the stream, either after a flush or because a filter like trim
+    /// doesn't pass on any more frames
+    EndOfStream,
+}
+
+#[derive(Error, Debug)]
+pub enum AVFilterGraphError {
+    #[error(transparent)]
+    AllocFailed(#[from] AVAllocError),
+    #[error("Filter {0} not found")]
+    FilterNotFound(String),
+    #[error("Filter description {0} contains null byte")]
+    DescriptionContainsNull(String, #[source] std::ffi::NulError),
+    #[error("Creating filter {0} failed")]
+    CreateFilterFailed(String, #[source] AVError),
+    #[error("Parsing filter description {0} failed")]
+    ParseFailed(String, #[source] AVError),
+    #[error("Configuring filter graph failed")]
+    ConfigureFailed(#[source] AVError),
+    #[error("Pushing frame into filter graph failed")]
+    PushFailed(#[source] AVError),
+    #[error("Pulling frame from filter graph failed")]
+    PullFailed(#[source] AVError),
+}
+
+impl AVFilterGraph {
+    /// Builds a filter graph from a filter description like "yadif,hflip", fed by a buffer source
+    /// matching the format and size of the given frame and drained through a buffer sink.
+    pub fn new(
+        description: &str,
+        source: &AVFrame,
+        time_base: &Fraction,
+    ) -> Result<Self, AVFilterGraphError> {
+        let base = unsafe { ffi::avfilter_graph_alloc() };
+        if base.is_null() {
+            return Err(AVAllocError::AllocFailed("AVFilterGraph".to_string()).into());
+        }
+        // From here on, the graph and all filters within it are freed on drop
+        let mut graph = AVFilterGraph {
+            base,
+            source: std::ptr::null_mut(),
+            sink: std::ptr::null_mut(),
+            width: source.width(),
+            height: source.height(),
+            format: source.format(),
+        };
+
+        let sample_aspect_ratio = source.as_ref().sample_aspect_ratio;
+        graph.source = graph.create_filter(
+            "buffer",
+            "in",
+            &format!(
+                "video_size={}x{}:pix_fmt={}:time_base={}/{}:pixel_aspect={}/{}",
+                source.width(),
+                source.height(),
+                source.format() as i32,
+                time_base.numer().copied().unwrap_or(0),
+                time_base.denom().copied().unwrap_or(1),
+                sample_aspect_ratio.num,
+                sample_aspect_ratio.den.max(1),
+            ),
+        )?;
+        graph.sink = graph.create_filter("buffersink", "out", "")?;
+
+        let c_description = std::ffi::CString::new(description).map_err(|err| {
+            AVFilterGraphError::DescriptionContainsNull(description.to_string(), err)
+        })?;
+
+        // The open ends of the parsed description are linked to our source and sink
+        let mut outputs = AVFilterGraph::inout("in", graph.source)?;
+        let mut inputs = match AVFilterGraph::inout("out", graph.sink) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                unsafe { ffi::avfilter_inout_free(&mut outputs) };
+                return Err(err);
+            }
+        };
+
+        let result = AVError::from_errno(unsafe {
+            ffi::avfilter_graph_parse_ptr(
+                graph.base,
+                c_description.as_ptr(),
+                &mut inputs,
+                &mut outputs,
+                std::ptr::null_mut(),
+            )
+        });
+        unsafe {
+            ffi::avfilter_inout_free(&mut inputs);
+            ffi::avfilter_inout_free(&mut outputs);
+        }
+        result.map_err(|err| AVFilterGraphError::ParseFailed(description.to_string(), err))?;
+
+        AVError::from_errno(unsafe { ffi::avfilter_graph_config(graph.base, std::ptr::null_mut()) })
+            .map_err(AVFilterGraphError::ConfigureFailed)?;
+
+        Ok(graph)
+    }
+
+    fn create_filter(
+        &mut self,
+        filter_name: &str,
+        name: &str,
+        args: &str,
+    ) -> Result<*mut ffi::AVFilterContext, AVFilterGraphError> {
+        let c_filter_name = std::ffi::CString::new(filter_name)
+            .map_err(|_| AVFilterGraphError::FilterNotFound(filter_name.to_string()))?;
+        let filter = unsafe { ffi::avfilter_get_by_name(c_filter_name.as_ptr()) };
+        if filter.is_null() {
+            return Err(AVFilterGraphError::FilterNotFound(filter_name.to_string()));
+        }
+
+        let c_name = std::ffi::CString::new(name)
+            .map_err(|err| AVFilterGraphError::DescriptionContainsNull(name.to_string(), err))?;
+        let c_args = std::ffi::CString::new(args)
+            .map_err(|err| AVFilterGraphError::DescriptionContainsNull(args.to_string(), err))?;
+
+        let mut context: *mut ffi::AVFilterContext = std::ptr::null_mut();
+        AVError::from_errno(unsafe {
+            ffi::avfilter_graph_create_filter(
+                &mut context,
+                filter,
+                c_name.as_ptr(),
+                if args.is_empty() { std::ptr::null() } else { c_args.as_ptr() },
+                std::ptr::null_mut(),
+                self.base,
+            )
+        })
+        .map_err(|err| AVFilterGraphError::CreateFilterFailed(filter_name.to_string(), err))?;
+
+        Ok(context)
+    }
+
+    fn inout(
+        name: &str,
+        context: *mut ffi::AVFilterContext,
+    ) -> Result<*mut ffi::AVFilterInOut, AVFilterGraphError> {
+        let mut inout = unsafe { ffi::avfilter_inout_alloc() };
+        if inout.is_null() {
+            return Err(AVAllocError::AllocFailed("AVFilterInOut".to_string()).into());
+        }
+
+        let c_name = std::ffi::CString::new(name)
+            .map_err(|err| AVFilterGraphError::DescriptionContainsNull(name.to_string(), err))?;
+        unsafe {
+            (*inout).name = ffi::av_strdup(c_name.as_ptr());
+            (*inout).filter_ctx = context;
+            (*inout).pad_idx = 0;
+            (*inout).next = std::ptr::null_mut();
+        }
+
+        if unsafe { (*inout).name.is_null() } {
+            unsafe { ffi::avfilter_inout_free(&mut inout) };
+            return Err(AVAllocError::AllocFailed("AVFilterInOut name".to_string()).into());
+        }
+
+        Ok(inout)
+    }
+
+    /// Whether the frame has the size and pixel format the buffer source was configured for
+    pub fn accepts(&self, frame: &AVFrame) -> bool {
+        frame.width() == self.width
+            && frame.height() == self.height
+            && frame.format() == self.format
+    }
+
+    /// Time base of the frames returned by [`AVFilterGraph::pull`]
+    pub fn time_base(&self) -> Fraction {
+        let time_base = unsafe { ffi::av_buffersink_get_time_base(self.sink) };
+        Fraction::new(time_base.num as u32, time_base.den as u32)
+    }
+
+    pub fn push(&mut self, frame: &mut AVFrame) -> Result<(), AVFilterGraphError> {
+        AVError::from_errno(unsafe {
+            ffi::av_buffersrc_add_frame_flags(
+                self.source,
+                frame.base,
+                ffi::AV_BUFFERSRC_FLAG_KEEP_REF as std::os::raw::c_int,
+            )
+        })
+        .map_err(AVFilterGraphError::PushFailed)
+    }
+
+    /// Signals the end of the input, so filters holding back frames can release them
+    pub fn flush(&mut self) -> Result<(), AVFilterGraphError> {
+        AVError::from_errno(unsafe {
+            ffi::av_buffersrc_add_frame_flags(self.source, std::ptr::null_mut(), 0)
+        })
+        .map_err(AVFilterGraphError::PushFailed)
+    }
+
+    pub fn pull(&mut self, frame: &mut AVFrame) -> Result<AVFilterStatus, AVFilterGraphError> {
+        unsafe { ffi::av_frame_unref(frame.base) }
+        match AVError::from_errno(unsafe { ffi::av_buffersink_get_frame(self.sink, frame.base) }) {
+            Ok(()) => Ok(AVFilterStatus::Frame),
+            Err(AVError::System(AvFfiError::TryAgain)) => Ok(AVFilterStatus::NeedsInput),
+            Err(AVError::Internal(AvInternalError::EndOfFile)) => Ok(AVFilterStatus::EndOfStream),
+            Err(err) => Err(AVFilterGraphError::PullFailed(err)),
+        }
+    }
+}
+
+impl Drop for AVFilterGraph {
+    fn drop(&mut self) {
+        unsafe { ffi::avfilter_graph_free(&mut self.base) }
+    }
+}
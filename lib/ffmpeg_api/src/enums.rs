@@ -404,6 +404,127 @@ pub enum AVPixelFormat {
     NV42 = ffi::AV_PIX_FMT_NV42,
 }
 
+#[doc = " Chromaticity coordinates of the source primaries."]
+#[derive(Debug, Copy, Clone, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum AVColorPrimaries {
+    #[doc = " also ITU-R BT1361 / IEC 61966-2-4 / SMPTE RP 177 Annex B"]
+    BT709 = ffi::AVCOL_PRI_BT709,
+    Unspecified = ffi::AVCOL_PRI_UNSPECIFIED,
+    #[doc = " also FCC Title 47 Code of Federal Regulations 73.682 (a)(20)"]
+    BT470M = ffi::AVCOL_PRI_BT470M,
+    #[doc = " also ITU-R BT601-6 625 / ITU-R BT1358 625 / ITU-R BT1700 625 PAL & SECAM"]
+    BT470BG = ffi::AVCOL_PRI_BT470BG,
+    #[doc = " also ITU-R BT601-6 525 / ITU-R BT1358 525 / ITU-R BT1700 NTSC"]
+    SMPTE170M = ffi::AVCOL_PRI_SMPTE170M,
+    #[doc = " identical to above, also called \"SMPTE C\" even though it uses D65"]
+    SMPTE240M = ffi::AVCOL_PRI_SMPTE240M,
+    #[doc = " colour filters using Illuminant C"]
+    Film = ffi::AVCOL_PRI_FILM,
+    #[doc = " ITU-R BT2020"]
+    BT2020 = ffi::AVCOL_PRI_BT2020,
+    #[doc = " SMPTE ST 428-1 (CIE 1931 XYZ)"]
+    SMPTE428 = ffi::AVCOL_PRI_SMPTE428,
+    #[doc = " SMPTE ST 431-2 (2011) / DCI P3"]
+    SMPTE431 = ffi::AVCOL_PRI_SMPTE431,
+    #[doc = " SMPTE ST 432-1 (2010) / P3 D65 / Display P3"]
+    SMPTE432 = ffi::AVCOL_PRI_SMPTE432,
+    #[doc = " EBU Tech. 3213-E (nothing there) / one of JEDEC P22 group phosphors"]
+    EBU3213 = ffi::AVCOL_PRI_EBU3213,
+}
+
+#[doc = " Color Transfer Characteristic."]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum AVColorTransferCharacteristic {
+    #[doc = " also ITU-R BT1361"]
+    BT709 = ffi::AVCOL_TRC_BT709,
+    Unspecified = ffi::AVCOL_TRC_UNSPECIFIED,
+    #[doc = " also ITU-R BT470M / ITU-R BT1700 625 PAL & SECAM"]
+    Gamma22 = ffi::AVCOL_TRC_GAMMA22,
+    #[doc = " also ITU-R BT470BG"]
+    Gamma28 = ffi::AVCOL_TRC_GAMMA28,
+    #[doc = " also ITU-R BT601-6 525 or 625 / ITU-R BT1358 525 or 625 / ITU-R BT1700 NTSC"]
+    SMPTE170M = ffi::AVCOL_TRC_SMPTE170M,
+    SMPTE240M = ffi::AVCOL_TRC_SMPTE240M,
+    #[doc = " \"Linear transfer characteristics\""]
+    Linear = ffi::AVCOL_TRC_LINEAR,
+    #[doc = " \"Logarithmic transfer characteristic (100:1 range)\""]
+    Log = ffi::AVCOL_TRC_LOG,
+    #[doc = " \"Logarithmic transfer characteristic (100 * Sqrt(10) : 1 range)\""]
+    LogSqrt = ffi::AVCOL_TRC_LOG_SQRT,
+    #[doc = " IEC 61966-2-4"]
+    IEC61966_2_4 = ffi::AVCOL_TRC_IEC61966_2_4,
+    #[doc = " ITU-R BT1361 Extended Colour Gamut"]
+    BT1361ECG = ffi::AVCOL_TRC_BT1361_ECG,
+    #[doc = " IEC 61966-2-1 (sRGB or sYCC)"]
+    IEC61966_2_1 = ffi::AVCOL_TRC_IEC61966_2_1,
+    #[doc = " ITU-R BT2020 for 10-bit system"]
+    BT2020_10 = ffi::AVCOL_TRC_BT2020_10,
+    #[doc = " ITU-R BT2020 for 12-bit system"]
+    BT2020_12 = ffi::AVCOL_TRC_BT2020_12,
+    #[doc = " SMPTE ST 2084 for 10-, 12-, 14- and 16-bit systems"]
+    SMPTE2084 = ffi::AVCOL_TRC_SMPTE2084,
+    #[doc = " SMPTE ST 428-1"]
+    SMPTE428 = ffi::AVCOL_TRC_SMPTE428,
+    #[doc = " ARIB STD-B67, known as \"Hybrid log-gamma\""]
+    AribStdB67 = ffi::AVCOL_TRC_ARIB_STD_B67,
+}
+
+impl AVColorTransferCharacteristic {
+    pub fn is_hdr(&self) -> bool {
+        matches!(
+            self,
+            AVColorTransferCharacteristic::SMPTE2084 | AVColorTransferCharacteristic::AribStdB67
+        )
+    }
+}
+
+#[doc = " YUV colorspace type."]
+#[derive(Debug, Copy, Clone, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum AVColorSpace {
+    #[doc = " order of coefficients is actually GBR, also IEC 61966-2-1 (sRGB)"]
+    RGB = ffi::AVCOL_SPC_RGB,
+    #[doc = " also ITU-R BT1361 / IEC 61966-2-4 xvYCC709 / derived in SMPTE RP 177 Annex B"]
+    BT709 = ffi::AVCOL_SPC_BT709,
+    Unspecified = ffi::AVCOL_SPC_UNSPECIFIED,
+    #[doc = " FCC Title 47 Code of Federal Regulations 73.682 (a)(20)"]
+    FCC = ffi::AVCOL_SPC_FCC,
+    #[doc = " also ITU-R BT601-6 625 / ITU-R BT1358 625 / ITU-R BT1700 625 PAL & SECAM / IEC 61966-2-4 xvYCC601"]
+    BT470BG = ffi::AVCOL_SPC_BT470BG,
+    #[doc = " also ITU-R BT601-6 525 / ITU-R BT1358 525 / ITU-R BT1700 NTSC / functionally identical to above"]
+    SMPTE170M = ffi::AVCOL_SPC_SMPTE170M,
+    #[doc = " derived from 170M primaries and D65 white point, 170M is derived from BT470 System M's primaries"]
+    SMPTE240M = ffi::AVCOL_SPC_SMPTE240M,
+    #[doc = " used by Dirac / VC-2 and H.264 FRext, see ITU-T SG16"]
+    YCGCO = ffi::AVCOL_SPC_YCGCO,
+    #[doc = " ITU-R BT2020 non-constant luminance system"]
+    BT2020NCL = ffi::AVCOL_SPC_BT2020_NCL,
+    #[doc = " ITU-R BT2020 constant luminance system"]
+    BT2020CL = ffi::AVCOL_SPC_BT2020_CL,
+    #[doc = " SMPTE 2085, Y'D'zD'x"]
+    SMPTE2085 = ffi::AVCOL_SPC_SMPTE2085,
+    #[doc = " Chromaticity-derived non-constant luminance system"]
+    ChromaDerivedNCL = ffi::AVCOL_SPC_CHROMA_DERIVED_NCL,
+    #[doc = " Chromaticity-derived constant luminance system"]
+    ChromaDerivedCL = ffi::AVCOL_SPC_CHROMA_DERIVED_CL,
+    #[doc = " ITU-R BT.2100-0, ICtCp"]
+    ICTCP = ffi::AVCOL_SPC_ICTCP,
+}
+
+#[doc = " Visual content value range."]
+#[derive(Debug, Copy, Clone, PartialEq, FromPrimitive, ToPrimitive)]
+#[repr(u32)]
+pub enum AVColorRange {
+    Unspecified = ffi::AVCOL_RANGE_UNSPECIFIED,
+    #[doc = " Narrow or limited range content, e.g. 16-235 for 8 bits"]
+    MPEG = ffi::AVCOL_RANGE_MPEG,
+    #[doc = " Full range content, e.g. 0-255 for 8 bits"]
+    JPEG = ffi::AVCOL_RANGE_JPEG,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, FromPrimitive, ToPrimitive)]
 #[repr(i32)]
 pub enum AVMediaType {
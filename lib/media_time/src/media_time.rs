@@ -9,6 +9,8 @@ pub enum MediaTimeError {
     TimebaseDenominatorMissing,
     #[error("invalid denominator in timebase")]
     TimebaseDenominatorInvalid,
+    #[error("invalid timestamp: {0}")]
+    TimestampInvalid(String),
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
@@ -37,6 +39,17 @@ impl MediaTime {
         MediaTime(time::Duration::seconds(timestamp))
     }
 
+    #[inline(always)]
+    pub fn from_seconds_f64(timestamp: f64) -> MediaTime {
+        MediaTime(time::Duration::seconds_f64(timestamp))
+    }
+
+    /// Returns None if the timestamp is not finite or out of range
+    #[inline(always)]
+    pub fn checked_from_seconds_f64(timestamp: f64) -> Option<MediaTime> {
+        time::Duration::checked_seconds_f64(timestamp).map(MediaTime)
+    }
+
     #[inline(always)]
     pub fn is_zero(&self) -> bool {
         self.0.is_zero()
@@ -79,6 +92,35 @@ impl std::fmt::Display for MediaTime {
     }
 }
 
+impl std::str::FromStr for MediaTime {
+    type Err = MediaTimeError;
+
+    /// Parses timestamps in the format written by Display, with optional hours
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || MediaTimeError::TimestampInvalid(String::from(s));
+        let (rest, millis) = s.split_once('.').ok_or_else(invalid)?;
+        if millis.len() != 3 {
+            return Err(invalid());
+        }
+        let millis: i64 = millis.parse().map_err(|_| invalid())?;
+
+        let parts = rest
+            .split(':')
+            .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<i64>, MediaTimeError>>()?;
+        let (h, m, s) = match parts[..] {
+            [m, s] => (0, m, s),
+            [h, m, s] => (h, m, s),
+            _ => return Err(invalid()),
+        };
+        if h < 0 || !(0..60).contains(&m) || !(0..60).contains(&s) || millis < 0 {
+            return Err(invalid());
+        }
+
+        Ok(MediaTime::from_millis(((h * 60 + m) * 60 + s) * 1000 + millis))
+    }
+}
+
 impl std::ops::Add for MediaTime {
     type Output = Self;
 
@@ -94,3 +136,19 @@ impl std::ops::Sub for MediaTime {
         Self(self.0 - other.0)
     }
 }
+
+impl std::ops::Mul<u32> for MediaTime {
+    type Output = Self;
+
+    fn mul(self, other: u32) -> Self {
+        Self(self.0 * other)
+    }
+}
+
+impl std::ops::Div<u32> for MediaTime {
+    type Output = Self;
+
+    fn div(self, other: u32) -> Self {
+        Self(self.0 / other)
+    }
+}
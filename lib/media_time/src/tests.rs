@@ -15,9 +15,48 @@ fn from_seconds_works() {
     assert_eq!(MediaTime::from_seconds(1357).milliseconds(), 1357000);
 }
 
+#[test]
+fn from_seconds_f64_works() {
+    assert_eq!(MediaTime::from_seconds_f64(0.0).milliseconds(), 0);
+    assert_eq!(MediaTime::from_seconds_f64(0.5).milliseconds(), 500);
+    assert_eq!(MediaTime::from_seconds_f64(1.25).milliseconds(), 1250);
+}
+
+#[test]
+fn checked_from_seconds_f64_works() {
+    assert_eq!(MediaTime::checked_from_seconds_f64(1.25).unwrap().milliseconds(), 1250);
+    assert_eq!(MediaTime::checked_from_seconds_f64(1e300), None);
+    assert_eq!(MediaTime::checked_from_seconds_f64(f64::NAN), None);
+}
+
 #[test]
 fn from_rational_works() {
     assert_eq!(MediaTime::from_rational(0, &Fraction::new(1u64, 1u64)).unwrap().seconds(), 0);
     assert_eq!(MediaTime::from_rational(1357, &Fraction::new(1u64, 1u64)).unwrap().seconds(), 1357);
     assert_eq!(MediaTime::from_rational(30, &Fraction::new(1u64, 3u64)).unwrap().seconds(), 10);
 }
+
+#[test]
+fn mul_div_works() {
+    assert_eq!((MediaTime::from_seconds(3) * 4).milliseconds(), 12000);
+    assert_eq!((MediaTime::from_seconds(10) / 4).milliseconds(), 2500);
+    assert_eq!((MediaTime::from_millis(1000) / 3 * 3).milliseconds(), 999);
+}
+
+#[test]
+fn from_str_works() {
+    assert_eq!("00:00.000".parse::<MediaTime>().unwrap().milliseconds(), 0);
+    assert_eq!("01:02.345".parse::<MediaTime>().unwrap().milliseconds(), 62345);
+    assert_eq!("01:00:02.345".parse::<MediaTime>().unwrap().milliseconds(), 3602345);
+    assert!("1:2".parse::<MediaTime>().is_err());
+    assert!("00:61.000".parse::<MediaTime>().is_err());
+    assert!("00:00.5".parse::<MediaTime>().is_err());
+}
+
+#[test]
+fn from_str_roundtrips_display() {
+    for millis in [0, 999, 59999, 3599999, 3600000, 86400123] {
+        let time = MediaTime::from_millis(millis);
+        assert_eq!(time.to_string().parse::<MediaTime>().unwrap(), time);
+    }
+}
@@ -0,0 +1,346 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use image::RgbImage;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use media_time::MediaTime;
+
+/// Side of the luma grid the DCT of a pHash is computed on
+const PHASH_SIZE: usize = 32;
+/// Side of the block of lowest frequencies a pHash keeps
+const PHASH_FREQUENCIES: usize = 8;
+/// Alignment slack used when the spacing of a sequence can't be determined
+const DEFAULT_SLACK_MILLIS: i128 = 1000;
+/// Smallest step of the coarse offset search, so dense sequences don't try millions of offsets
+const MIN_STEP_MILLIS: i128 = DEFAULT_SLACK_MILLIS / 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// Difference hash comparing neighbouring pixels, fast and robust against scaling
+    DHash,
+    /// DCT based hash, more robust against compression and color changes
+    PHash,
+}
+
+impl HashAlgorithm {
+    pub fn hash(&self, image: &RgbImage) -> u64 {
+        match self {
+            HashAlgorithm::DHash => dhash(image),
+            HashAlgorithm::PHash => phash(image),
+        }
+    }
+}
+
+fn luma(image: &RgbImage, width: u32, height: u32) -> Vec<f64> {
+    let gray = image::imageops::grayscale(image);
+    image::imageops::resize(&gray, width, height, FilterType::Triangle)
+        .pixels()
+        .map(|pixel| pixel.0[0] as f64)
+        .collect()
+}
+
+/// Sets a bit for every pixel of a 9×8 luma grid that is darker than its right neighbour
+pub fn dhash(image: &RgbImage) -> u64 {
+    let pixels = luma(image, 9, 8);
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            if pixels[y * 9 + x] < pixels[y * 9 + x + 1] {
+                hash |= 1 << (y * 8 + x);
+            }
+        }
+    }
+    hash
+}
+
+/// Sets a bit for every one of the 8×8 lowest DCT frequencies of a 32×32 luma grid that is
+/// above their median
+pub fn phash(image: &RgbImage) -> u64 {
+    let pixels = luma(image, PHASH_SIZE as u32, PHASH_SIZE as u32);
+    let cosines: Vec<f64> = (0..PHASH_FREQUENCIES * PHASH_SIZE)
+        .map(|index| {
+            let (frequency, position) = (index / PHASH_SIZE, index % PHASH_SIZE);
+            (std::f64::consts::PI * (2 * position + 1) as f64 * frequency as f64
+                / (2 * PHASH_SIZE) as f64)
+                .cos()
+        })
+        .collect();
+    let cosine = |frequency: usize, position: usize| cosines[frequency * PHASH_SIZE + position];
+
+    // The 2D DCT is separable, so rows are transformed first and their results by column
+    let mut rows = vec![0.0; PHASH_SIZE * PHASH_FREQUENCIES];
+    for y in 0..PHASH_SIZE {
+        for u in 0..PHASH_FREQUENCIES {
+            rows[y * PHASH_FREQUENCIES + u] = (0..PHASH_SIZE)
+                .map(|x| pixels[y * PHASH_SIZE + x] * cosine(u, x))
+                .sum();
+        }
+    }
+    let mut coefficients = vec![0.0; PHASH_FREQUENCIES * PHASH_FREQUENCIES];
+    for v in 0..PHASH_FREQUENCIES {
+        for u in 0..PHASH_FREQUENCIES {
+            coefficients[v * PHASH_FREQUENCIES + u] = (0..PHASH_SIZE)
+                .map(|y| rows[y * PHASH_FREQUENCIES + u] * cosine(v, y))
+                .sum();
+        }
+    }
+
+    // The DC coefficient only reflects the average brightness, so it's left out of the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, coefficient)| **coefficient > median)
+        .fold(0, |hash, (index, _)| hash | 1 << index)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameHash {
+    pub timestamp: MediaTime,
+    pub hash: u64,
+}
+
+/// Result of matching two hash sequences
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Comparison {
+    /// Time to add to timestamps of the first sequence to find the same frame in the second
+    pub offset: MediaTime,
+    /// Mean share of equal bits between matched frames, between 0 and 1
+    pub similarity: f64,
+    /// Share of frames of the first sequence that have a counterpart in the second
+    pub coverage: f64,
+}
+
+/// Perceptual hashes of the sprites of an extraction, in presentation order
+pub struct HashSequence {
+    algorithm: HashAlgorithm,
+    frames: Vec<FrameHash>,
+}
+
+#[derive(Error, Debug)]
+pub enum HashError {
+    #[error("Error saving file {0}")]
+    IoError(PathBuf, #[source] std::io::Error),
+    #[error("Error loading file {0}")]
+    LoadError(PathBuf, #[source] std::io::Error),
+    #[error("Invalid file {0}: {1}")]
+    InvalidFile(PathBuf, String),
+}
+
+/// Serialized form with timestamps in milliseconds and hashes as hex strings, as JSON numbers
+/// can't hold 64 bit integers in most parsers
+#[derive(Serialize, Deserialize)]
+struct SequenceFile {
+    algorithm: HashAlgorithm,
+    frames: Vec<FrameFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameFile {
+    timestamp: i64,
+    hash: String,
+}
+
+impl HashSequence {
+    pub fn new(algorithm: HashAlgorithm) -> HashSequence {
+        HashSequence {
+            algorithm,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Hashes an image, frames have to be added in presentation order
+    pub fn add_image(&mut self, timestamp: MediaTime, image: &RgbImage) {
+        self.add(timestamp, self.algorithm.hash(image));
+    }
+
+    pub fn add(&mut self, timestamp: MediaTime, hash: u64) {
+        self.frames.push(FrameHash { timestamp, hash });
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn frames(&self) -> &[FrameHash] {
+        &self.frames
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.frames.truncate(len);
+    }
+
+    pub(crate) fn parse(data: &str) -> Result<HashSequence, String> {
+        let file: SequenceFile = serde_json::from_str(data).map_err(|err| err.to_string())?;
+        let frames = file
+            .frames
+            .into_iter()
+            .map(|frame| {
+                let hash = u64::from_str_radix(&frame.hash, 16)
+                    .map_err(|_| format!("invalid hash {}", frame.hash))?;
+                Ok(FrameHash {
+                    timestamp: MediaTime::from_millis(frame.timestamp),
+                    hash,
+                })
+            })
+            .collect::<Result<Vec<FrameHash>, String>>()?;
+        Ok(HashSequence {
+            algorithm: file.algorithm,
+            frames,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<HashSequence, HashError> {
+        let data = std::fs::read_to_string(&path)
+            .map_err(|err| HashError::LoadError(path.as_ref().to_path_buf(), err))?;
+        HashSequence::parse(&data)
+            .map_err(|err| HashError::InvalidFile(path.as_ref().to_path_buf(), err))
+    }
+
+    pub(crate) fn write(&self, writer: &mut impl Write) -> Result<(), std::io::Error> {
+        let file = SequenceFile {
+            algorithm: self.algorithm,
+            frames: self
+                .frames
+                .iter()
+                .map(|frame| FrameFile {
+                    timestamp: frame.timestamp.milliseconds() as i64,
+                    hash: format!("{:016x}", frame.hash),
+                })
+                .collect(),
+        };
+        serde_json::to_writer_pretty(&mut *writer, &file)?;
+        writer.flush()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), HashError> {
+        File::create(&path)
+            .and_then(|file| self.write(&mut BufWriter::new(file)))
+            .map_err(|err| HashError::IoError(path.as_ref().to_path_buf(), err))
+    }
+
+    /// Half the median time between two frames, a frame closer than this to the position it is
+    /// looked for at is considered to be there
+    fn slack(&self) -> i128 {
+        let mut spacings: Vec<i128> = self
+            .frames
+            .windows(2)
+            .map(|pair| (pair[1].timestamp - pair[0].timestamp).milliseconds())
+            .collect();
+        spacings.sort();
+        match spacings.get(spacings.len() / 2) {
+            Some(spacing) => (spacing / 2).max(1),
+            None => DEFAULT_SLACK_MILLIS,
+        }
+    }
+
+    /// Matches every frame to the frame of the other sequence closest to its timestamp plus
+    /// the offset, if there is one closer than the slack. Returns the comparison along with the
+    /// total distance in milliseconds between the matched frames and their positions.
+    fn compare_at(
+        &self,
+        other: &HashSequence,
+        offset: i128,
+        slack: i128,
+    ) -> Option<(Comparison, i128)> {
+        let mut matched = 0;
+        let mut similarity = 0.0;
+        let mut distance = 0;
+        for frame in &self.frames {
+            let target = frame.timestamp.milliseconds() + offset;
+            let index = other
+                .frames
+                .partition_point(|candidate| candidate.timestamp.milliseconds() < target);
+            let nearest = [index.checked_sub(1), Some(index)]
+                .into_iter()
+                .flatten()
+                .filter_map(|index| other.frames.get(index))
+                .map(|candidate| {
+                    (
+                        candidate,
+                        (candidate.timestamp.milliseconds() - target).abs(),
+                    )
+                })
+                .min_by_key(|(_, distance)| *distance);
+            if let Some((nearest, nearest_distance)) = nearest
+                && nearest_distance < slack
+            {
+                matched += 1;
+                similarity += 1.0 - (frame.hash ^ nearest.hash).count_ones() as f64 / 64.0;
+                distance += nearest_distance;
+            }
+        }
+        let comparison = Comparison {
+            offset: MediaTime::from_millis(offset as i64),
+            similarity: similarity / matched as f64,
+            coverage: matched as f64 / self.frames.len() as f64,
+        };
+        (matched > 0).then_some((comparison, distance))
+    }
+
+    /// Compares two sequences, e.g. of a file and a suspected re-encode, trying offsets of up
+    /// to `max_offset` in both directions to account for trimmed or shifted content. Offsets are
+    /// tried in steps of at least 250 ms first, then refined around the best one in steps of a
+    /// quarter of the frame spacing of the other sequence. The one with the highest similarity
+    /// weighted by coverage wins, on ties the one aligning the frames best.
+    /// Returns None if the sequences use different algorithms or no frames match.
+    pub fn compare(&self, other: &HashSequence, max_offset: MediaTime) -> Option<Comparison> {
+        if self.algorithm != other.algorithm {
+            return None;
+        }
+        let slack = other.slack();
+        let max_offset = max_offset.milliseconds().max(0);
+        let fine_step = (slack / 2).max(1);
+        let coarse_step = fine_step.max(MIN_STEP_MILLIS);
+
+        let coarse = (0..=max_offset / coarse_step)
+            .flat_map(|step| [step * coarse_step, -step * coarse_step]);
+        let (coarse, _) = self.best_offset(other, slack, coarse)?;
+        let center = coarse.offset.milliseconds();
+        let fine = (1..coarse_step / fine_step)
+            .flat_map(|step| [center + step * fine_step, center - step * fine_step])
+            .filter(|offset| offset.abs() <= max_offset);
+        self.best_offset(other, slack, std::iter::once(center).chain(fine))
+            .map(|(comparison, _)| comparison)
+    }
+
+    /// Returns the comparison at the best of the offsets along with its total distance, the
+    /// earlier offset on ties
+    fn best_offset(
+        &self,
+        other: &HashSequence,
+        slack: i128,
+        offsets: impl Iterator<Item = i128>,
+    ) -> Option<(Comparison, i128)> {
+        let mut best: Option<(Comparison, i128)> = None;
+        for offset in offsets {
+            let Some((comparison, distance)) = self.compare_at(other, offset, slack) else {
+                continue;
+            };
+            let score = comparison.similarity * comparison.coverage;
+            let better = best.is_none_or(|(best, best_distance)| {
+                let best_score = best.similarity * best.coverage;
+                score > best_score || (score == best_score && distance < best_distance)
+            });
+            if better {
+                best = Some((comparison, distance));
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,6 @@
+#[cfg(test)]
+mod tests;
+
+mod hash;
+
+pub use hash::{Comparison, FrameHash, HashAlgorithm, HashError, HashSequence, dhash, phash};
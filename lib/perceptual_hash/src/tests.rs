@@ -0,0 +1,114 @@
+use image::imageops::FilterType;
+use image::{Rgb, RgbImage};
+use media_time::MediaTime;
+
+use crate::{HashAlgorithm, HashSequence, dhash, phash};
+
+/// Smooth pattern of low frequency waves, different for every seed
+fn pattern(width: u32, height: u32, seed: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        let x = x as f64 / width as f64 * std::f64::consts::TAU;
+        let y = y as f64 / height as f64 * std::f64::consts::TAU;
+        let wave = (x * (seed % 3 + 1) as f64 + seed as f64).sin()
+            * (y * (seed % 4 + 1) as f64 + seed as f64 * 0.7).cos();
+        let value = (127.5 + 127.5 * wave) as u8;
+        Rgb([value, value / 2, 255 - value])
+    })
+}
+
+fn sequence(algorithm: HashAlgorithm, seeds: &[u32], start: i64) -> HashSequence {
+    let mut sequence = HashSequence::new(algorithm);
+    for (index, &seed) in seeds.iter().enumerate() {
+        let timestamp = MediaTime::from_seconds(start + 2 * index as i64);
+        sequence.add_image(timestamp, &pattern(160, 90, seed));
+    }
+    sequence
+}
+
+#[test]
+fn dhash_is_stable_under_scaling() {
+    let large = pattern(320, 180, 3);
+    let small = image::imageops::resize(&large, 160, 90, FilterType::Nearest);
+    let (large, small) = (dhash(&large), dhash(&small));
+    assert!((large ^ small).count_ones() <= 4);
+}
+
+#[test]
+fn phash_differs_for_different_images() {
+    let first = phash(&pattern(160, 90, 3));
+    let second = phash(&pattern(160, 90, 11));
+    assert_eq!(first, phash(&pattern(160, 90, 3)));
+    assert!((first ^ second).count_ones() > 10);
+}
+
+#[test]
+fn write_and_parse_roundtrip() {
+    let mut sequence = HashSequence::new(HashAlgorithm::PHash);
+    sequence.add(MediaTime::from_millis(0), 0);
+    sequence.add(MediaTime::from_millis(2500), 0xfedc_ba98_7654_3210);
+
+    let mut data = Vec::new();
+    sequence.write(&mut data).unwrap();
+    let data = String::from_utf8(data).unwrap();
+    let loaded = HashSequence::parse(&data).unwrap();
+    assert!(data.contains("\"algorithm\": \"phash\""));
+    assert!(data.contains("\"hash\": \"fedcba9876543210\""));
+    assert_eq!(loaded.algorithm(), HashAlgorithm::PHash);
+    assert_eq!(loaded.frames(), sequence.frames());
+}
+
+#[test]
+fn parse_rejects_invalid_hashes() {
+    let result =
+        HashSequence::parse(r#"{"algorithm":"dhash","frames":[{"timestamp":0,"hash":"xyz"}]}"#);
+    assert!(result.is_err());
+}
+
+#[test]
+fn compare_finds_time_offset() {
+    let seeds = [3, 5, 11, 13, 17, 19, 23, 29, 31, 37];
+    let original = sequence(HashAlgorithm::DHash, &seeds, 0);
+    // The copy has four seconds of extra content in front and lost the first sprite
+    let shifted = sequence(HashAlgorithm::DHash, &seeds[1..], 6);
+
+    let comparison = original
+        .compare(&shifted, MediaTime::from_seconds(10))
+        .unwrap();
+    assert_eq!(comparison.offset, MediaTime::from_seconds(4));
+    assert_eq!(comparison.similarity, 1.0);
+    assert_eq!(comparison.coverage, 0.9);
+
+    let comparison = original
+        .compare(&shifted, MediaTime::from_seconds(0))
+        .unwrap();
+    assert!(comparison.similarity < 1.0);
+}
+
+#[test]
+fn compare_refines_offset_of_dense_sequences() {
+    // Frames every 20ms of scenes lasting 500ms each, the copy starting 130ms later
+    let dense = |start: i64| {
+        let mut sequence = HashSequence::new(HashAlgorithm::DHash);
+        for index in 0..400u64 {
+            let hash = (index / 25 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            sequence.add(MediaTime::from_millis(start + index as i64 * 20), hash);
+        }
+        sequence
+    };
+    let comparison = dense(0)
+        .compare(&dense(130), MediaTime::from_seconds(10))
+        .unwrap();
+    assert_eq!(comparison.offset, MediaTime::from_millis(130));
+    assert_eq!(comparison.similarity, 1.0);
+}
+
+#[test]
+fn compare_rejects_different_algorithms() {
+    let first = sequence(HashAlgorithm::DHash, &[3, 5], 0);
+    let second = sequence(HashAlgorithm::PHash, &[3, 5], 0);
+    assert!(
+        first
+            .compare(&second, MediaTime::from_seconds(10))
+            .is_none()
+    );
+}
@@ -1,4 +1,70 @@
+use media_time::MediaTime;
+
+use crate::{WebVTTCue, WebVTTFile};
+
 #[test]
 fn it_works() {
     assert_eq!(2 + 2, 4);
 }
+
+fn write(file: &WebVTTFile) -> String {
+    let mut data = Vec::new();
+    file.write(&mut data).unwrap();
+    String::from_utf8(data).unwrap()
+}
+
+#[test]
+fn write_puts_notes_before_cues() {
+    let mut file = WebVTTFile::new();
+    file.add_note("crop=0,140,1920,800");
+    file.add(WebVTTCue::new(
+        MediaTime::from_millis(0),
+        MediaTime::from_millis(2500),
+        String::from("preview_0.jpeg#xywh=0,0,240,100"),
+    ));
+
+    assert_eq!(
+        write(&file),
+        "WEBVTT\n\nNOTE crop=0,140,1920,800\n\n00:00.000 --> 00:02.500\npreview_0.jpeg#xywh=0,0,240,100\n\n"
+    );
+}
+
+#[test]
+fn parse_reads_written_file() {
+    let mut file = WebVTTFile::new();
+    file.add_note("duration unknown");
+    file.add(WebVTTCue::new(
+        MediaTime::from_millis(0),
+        MediaTime::from_millis(2500),
+        String::from("preview_0.jpeg#xywh=0,0,240,100"),
+    ));
+    file.add(WebVTTCue::new(
+        MediaTime::from_millis(2500),
+        MediaTime::from_millis(3600000),
+        String::from("preview_0.jpeg#xywh=240,0,240,100"),
+    ));
+
+    let loaded = WebVTTFile::parse(&write(&file)).unwrap();
+    assert_eq!(loaded.notes(), ["duration unknown"]);
+    assert_eq!(loaded.cues().len(), 2);
+    assert_eq!(loaded.cues()[1].start(), MediaTime::from_millis(2500));
+    assert_eq!(loaded.cues()[1].end(), MediaTime::from_millis(3600000));
+    assert_eq!(loaded.cues()[1].payload(), "preview_0.jpeg#xywh=240,0,240,100");
+}
+
+#[test]
+fn parse_skips_identifiers_and_settings() {
+    let loaded = WebVTTFile::parse(
+        "WEBVTT - sprites\r\n\r\n1\r\n00:01.000 --> 00:02.000 align:start\r\nfirst\r\nline\r\n",
+    )
+    .unwrap();
+    assert_eq!(loaded.cues().len(), 1);
+    assert_eq!(loaded.cues()[0].start(), MediaTime::from_millis(1000));
+    assert_eq!(loaded.cues()[0].end(), MediaTime::from_millis(2000));
+    assert_eq!(loaded.cues()[0].payload(), "first\nline");
+}
+
+#[test]
+fn parse_rejects_missing_header() {
+    assert!(WebVTTFile::parse("00:01.000 --> 00:02.000\nfirst\n").is_err());
+}
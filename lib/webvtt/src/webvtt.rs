@@ -9,9 +9,11 @@ use thiserror::Error;
 use media_time::MediaTime;
 
 pub struct WebVTTFile {
+    notes: Vec<String>,
     cues: Vec<WebVTTCue>,
 }
 
+#[derive(Clone)]
 pub struct WebVTTCue {
     start: MediaTime,
     end: MediaTime,
@@ -22,30 +24,102 @@ pub struct WebVTTCue {
 pub enum WebVTTError {
     #[error("Error saving file {0}")]
     IoError(PathBuf, #[source] std::io::Error),
+    #[error("Error loading file {0}")]
+    LoadError(PathBuf, #[source] std::io::Error),
+    #[error("Invalid file {0}: {1}")]
+    InvalidFile(PathBuf, String),
 }
 
 impl WebVTTFile {
     pub fn new() -> WebVTTFile {
-        WebVTTFile { cues: Vec::new() }
+        WebVTTFile {
+            notes: Vec::new(),
+            cues: Vec::new(),
+        }
+    }
+
+    /// Adds a comment block, written before all cues and ignored by players
+    pub fn add_note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
     }
 
     pub fn add(&mut self, cue: WebVTTCue) {
         self.cues.push(cue);
     }
 
-    fn save_impl(&self, path: &impl AsRef<Path>) -> Result<(), std::io::Error> {
-        let file = File::create(path)?;
-        let mut file = LineWriter::new(file);
-        file.write_all(b"WEBVTT\n\n")?;
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn cues(&self) -> &[WebVTTCue] {
+        &self.cues
+    }
+
+    /// Removes and returns the last cue
+    pub fn pop(&mut self) -> Option<WebVTTCue> {
+        self.cues.pop()
+    }
+
+    /// Keeps only the first `len` cues
+    pub fn truncate(&mut self, len: usize) {
+        self.cues.truncate(len);
+    }
+
+    pub(crate) fn parse(content: &str) -> Result<WebVTTFile, String> {
+        let content = content.replace("\r\n", "\n");
+        let mut blocks = content
+            .split("\n\n")
+            .map(|block| block.trim_matches('\n'))
+            .filter(|block| !block.is_empty());
+
+        match blocks.next() {
+            Some(header) if header.starts_with("WEBVTT") => {}
+            _ => return Err(String::from("missing WEBVTT header")),
+        }
+
+        let mut file = WebVTTFile::new();
+        for block in blocks {
+            if let Some(note) = block.strip_prefix("NOTE") {
+                file.add_note(note.trim_start());
+                continue;
+            }
+
+            // Cues may be preceded by an identifier line, which is dropped
+            let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+            let timing = lines
+                .next()
+                .ok_or_else(|| format!("block without cue timing: {}", block))?;
+            let (start, end) = timing.split_once("-->").unwrap_or_default();
+            let end = end.split_whitespace().next().unwrap_or_default();
+            let start = start.trim().parse().map_err(|err| format!("{}", err))?;
+            let end = end.parse().map_err(|err| format!("{}", err))?;
+            file.add(WebVTTCue::new(start, end, lines.collect::<Vec<_>>().join("\n")));
+        }
+        Ok(file)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<WebVTTFile, WebVTTError> {
+        let content = std::fs::read_to_string(&path)
+            .map_err(|err| WebVTTError::LoadError(path.as_ref().to_path_buf(), err))?;
+        WebVTTFile::parse(&content)
+            .map_err(|err| WebVTTError::InvalidFile(path.as_ref().to_path_buf(), err))
+    }
+
+    pub(crate) fn write(&self, writer: &mut impl Write) -> Result<(), std::io::Error> {
+        writer.write_all(b"WEBVTT\n\n")?;
+        for note in &self.notes {
+            writer.write_all(format!("NOTE {}\n\n", note).as_bytes())?;
+        }
         for cue in &self.cues {
-            cue.save(&mut file)?;
+            cue.write(writer)?;
         }
-        file.flush()?;
+        writer.flush()?;
         Ok(())
     }
 
     pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WebVTTError> {
-        self.save_impl(&path.as_ref())
+        File::create(&path)
+            .and_then(|file| self.write(&mut LineWriter::new(file)))
             .map_err(|err| WebVTTError::IoError(path.as_ref().to_path_buf(), err))
     }
 }
@@ -59,7 +133,19 @@ impl WebVTTCue {
         }
     }
 
-    fn save(&self, writer: &mut LineWriter<File>) -> Result<(), std::io::Error> {
+    pub fn start(&self) -> MediaTime {
+        self.start
+    }
+
+    pub fn end(&self) -> MediaTime {
+        self.end
+    }
+
+    pub fn payload(&self) -> &str {
+        &self.payload
+    }
+
+    fn write(&self, writer: &mut impl Write) -> Result<(), std::io::Error> {
         writer.write_all(format!("{} --> {}\n", self.start, self.end).as_bytes())?;
         writer.write_all(self.payload.as_bytes())?;
         writer.write_all(b"\n\n")?;
@@ -0,0 +1,364 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use anyhow::{bail, format_err, Error};
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder, RgbImage};
+use media_time::MediaTime;
+
+/// Browsers replace shorter frame delays with 100ms, which would slow the preview down
+const MIN_FRAME_DELAY: u32 = 20;
+/// Palette quantization speed between 1 (best quality) and 30 (fastest)
+const GIF_SPEED: i32 = 10;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AnimationFormat {
+    /// GIF with a quantized palette per frame
+    Gif,
+    /// Animated lossless WebP
+    WebP,
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::WebP => "webp",
+            AnimationFormat::Apng => "png",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FrameDelay {
+    /// Play the sprites back this many times faster than the input
+    Speed(f64),
+    /// Show every sprite for the same time
+    Fixed(MediaTime),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Animation {
+    pub format: AnimationFormat,
+    pub frame_delay: FrameDelay,
+}
+
+/// Animation file after its header was written
+enum Output<W: Write + Seek> {
+    Gif(gif::Encoder<W>),
+    /// Position of the RIFF header, whose size is filled in at the end
+    WebP { writer: W, start: u64 },
+    /// Position of the acTL chunk, whose frame count is filled in at the end, and the sequence
+    /// number of the next fcTL or fdAT chunk
+    Apng {
+        writer: W,
+        actl: u64,
+        sequence: u32,
+        frames: u32,
+    },
+}
+
+/// Encodes the sprites of an extraction as an animated preview while they are added. Only the
+/// latest sprite is kept, as the time it is shown depends on the timestamp of the next one.
+pub struct AnimationEncoder<W: Write + Seek> {
+    options: Animation,
+    /// Writer until the first frame arrives, as the header needs its size
+    writer: Option<W>,
+    output: Option<Output<W>>,
+    pending: Option<(MediaTime, RgbImage)>,
+}
+
+/// Writes a chunk of a RIFF container, padded to an even size
+fn write_riff_chunk(
+    writer: &mut impl Write,
+    fourcc: &[u8; 4],
+    payload: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(fourcc)?;
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)?;
+    if payload.len() % 2 == 1 {
+        writer.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+/// Writes a PNG chunk followed by the CRC of its type and data
+fn write_png_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    writer.write_all(&crc.finalize().to_be_bytes())
+}
+
+/// Little endian 24 bit integer as used by the WebP container
+fn u24(value: u32) -> [u8; 3] {
+    let [a, b, c, _] = value.to_le_bytes();
+    [a, b, c]
+}
+
+/// Returns the VP8L chunk of a lossless still WebP, checking it is the only chunk of the file
+fn vp8l_chunk(still: &[u8]) -> Option<&[u8]> {
+    if still.get(..4)? != b"RIFF" || still.get(8..12)? != b"WEBP" || still.get(12..16)? != b"VP8L"
+    {
+        return None;
+    }
+    let size = u32::from_le_bytes(still.get(16..20)?.try_into().ok()?) as usize;
+    let end = 20 + size + size % 2;
+    (still.len() == end).then(|| &still[12..])
+}
+
+/// Concatenates the data of the IDAT chunks of a still PNG
+fn idat_data(still: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut rest = still.strip_prefix(&PNG_SIGNATURE[..])?;
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let chunk = rest.get(8..8 + length)?;
+        if rest.get(4..8)? == b"IDAT" {
+            data.extend_from_slice(chunk);
+        }
+        rest = rest.get(12 + length..)?;
+    }
+    Some(data)
+}
+
+fn encode_error(err: impl std::fmt::Display) -> Error {
+    format_err!("Could not encode animation: {}", err)
+}
+
+impl<W: Write + Seek> AnimationEncoder<W> {
+    pub fn new(options: Animation, writer: W) -> AnimationEncoder<W> {
+        AnimationEncoder {
+            options,
+            writer: Some(writer),
+            output: None,
+            pending: None,
+        }
+    }
+
+    pub fn options(&self) -> &Animation {
+        &self.options
+    }
+
+    /// Adds a sprite, writing the previous one now that its display time is known
+    pub fn add(&mut self, timestamp: MediaTime, image: &RgbImage) -> Result<(), Error> {
+        if let Some((start, previous)) = self.pending.replace((timestamp, image.clone())) {
+            self.write_frame(&previous, self.delay(start, timestamp))?;
+        }
+        Ok(())
+    }
+
+    /// Display time of a frame in milliseconds
+    fn delay(&self, start: MediaTime, end: MediaTime) -> u32 {
+        let millis = match self.options.frame_delay {
+            FrameDelay::Speed(speed) => {
+                ((end - start).milliseconds() as f64 / speed).round() as i128
+            }
+            FrameDelay::Fixed(delay) => delay.milliseconds(),
+        };
+        (millis.clamp(0, u32::MAX.into()) as u32).max(MIN_FRAME_DELAY)
+    }
+
+    fn write_frame(&mut self, image: &RgbImage, delay: u32) -> Result<(), Error> {
+        if let Some(writer) = self.writer.take() {
+            self.output = Some(self.write_header(writer, image)?);
+        }
+        match &mut self.output {
+            Some(Output::Gif(encoder)) => Self::write_gif_frame(encoder, image, delay),
+            Some(Output::WebP { writer, .. }) => Self::write_webp_frame(writer, image, delay),
+            Some(Output::Apng {
+                writer,
+                sequence,
+                frames,
+                ..
+            }) => {
+                *sequence = Self::write_apng_frame(writer, image, delay, *sequence, *frames)?;
+                *frames += 1;
+                Ok(())
+            }
+            None => bail!("Could not encode animation, writing its header failed"),
+        }
+    }
+
+    fn write_header(&self, mut writer: W, image: &RgbImage) -> Result<Output<W>, Error> {
+        let (width, height) = image.dimensions();
+        match self.options.format {
+            AnimationFormat::Gif => {
+                let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+                    bail!(
+                        "Could not encode animation, {}x{} exceeds the GIF size limit",
+                        width,
+                        height
+                    );
+                };
+                let mut encoder =
+                    gif::Encoder::new(writer, width, height, &[]).map_err(encode_error)?;
+                encoder.set_repeat(gif::Repeat::Infinite).map_err(encode_error)?;
+                Ok(Output::Gif(encoder))
+            }
+            AnimationFormat::WebP => {
+                let start = writer.stream_position().map_err(encode_error)?;
+                let mut vp8x = vec![0x02, 0, 0, 0];
+                vp8x.extend_from_slice(&u24(width - 1));
+                vp8x.extend_from_slice(&u24(height - 1));
+                // The RIFF size is a placeholder until all frames are written
+                writer
+                    .write_all(b"RIFF\0\0\0\0WEBP")
+                    .and_then(|_| write_riff_chunk(&mut writer, b"VP8X", &vp8x))
+                    // Black background, looping infinitely
+                    .and_then(|_| write_riff_chunk(&mut writer, b"ANIM", &[0, 0, 0, 0xff, 0, 0]))
+                    .map_err(encode_error)?;
+                Ok(Output::WebP { writer, start })
+            }
+            AnimationFormat::Apng => {
+                let mut ihdr = Vec::with_capacity(13);
+                ihdr.extend_from_slice(&width.to_be_bytes());
+                ihdr.extend_from_slice(&height.to_be_bytes());
+                // 8 bit RGB, without interlacing
+                ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+                writer
+                    .write_all(&PNG_SIGNATURE)
+                    .and_then(|_| write_png_chunk(&mut writer, b"IHDR", &ihdr))
+                    .map_err(encode_error)?;
+                // The frame count is a placeholder until all frames are written
+                let actl = writer.stream_position().map_err(encode_error)?;
+                write_png_chunk(&mut writer, b"acTL", &[0; 8]).map_err(encode_error)?;
+                Ok(Output::Apng {
+                    writer,
+                    actl,
+                    sequence: 0,
+                    frames: 0,
+                })
+            }
+        }
+    }
+
+    fn write_gif_frame(
+        encoder: &mut gif::Encoder<W>,
+        image: &RgbImage,
+        delay: u32,
+    ) -> Result<(), Error> {
+        let (width, height) = image.dimensions();
+        let mut frame =
+            gif::Frame::from_rgb_speed(width as u16, height as u16, image.as_raw(), GIF_SPEED);
+        // GIF delays are in hundredths of a second
+        frame.delay = (delay / 10).min(u16::MAX.into()) as u16;
+        encoder.write_frame(&frame).map_err(encode_error)
+    }
+
+    /// Encodes the frame as a lossless still image and wraps its VP8L chunk in an ANMF chunk
+    fn write_webp_frame(writer: &mut W, image: &RgbImage, delay: u32) -> Result<(), Error> {
+        let (width, height) = image.dimensions();
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still)
+            .write_image(image.as_raw(), width, height, ExtendedColorType::Rgb8)
+            .map_err(encode_error)?;
+        let vp8l = vp8l_chunk(&still)
+            .ok_or_else(|| encode_error("still WebP frame is not a single VP8L chunk"))?;
+
+        let mut frame = Vec::with_capacity(16 + vp8l.len());
+        frame.extend_from_slice(&u24(0));
+        frame.extend_from_slice(&u24(0));
+        frame.extend_from_slice(&u24(width - 1));
+        frame.extend_from_slice(&u24(height - 1));
+        frame.extend_from_slice(&u24(delay.min(0xff_ffff)));
+        // Frames cover the whole canvas, so they replace the previous one without blending
+        frame.push(0x02);
+        frame.extend_from_slice(vp8l);
+        write_riff_chunk(writer, b"ANMF", &frame).map_err(encode_error)
+    }
+
+    /// Encodes the frame as a still PNG and writes its image data after a frame control chunk,
+    /// as IDAT for the first frame and fdAT for the others. Returns the next sequence number.
+    fn write_apng_frame(
+        writer: &mut W,
+        image: &RgbImage,
+        delay: u32,
+        mut sequence: u32,
+        index: u32,
+    ) -> Result<u32, Error> {
+        let (width, height) = image.dimensions();
+        let mut still = Vec::new();
+        let mut encoder = png::Encoder::new(&mut still, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut png_writer = encoder.write_header().map_err(encode_error)?;
+        png_writer.write_image_data(image.as_raw()).map_err(encode_error)?;
+        png_writer.finish().map_err(encode_error)?;
+        let data = idat_data(&still).ok_or_else(|| encode_error("still PNG frame is invalid"))?;
+
+        let mut fctl = Vec::with_capacity(26);
+        fctl.extend_from_slice(&sequence.to_be_bytes());
+        fctl.extend_from_slice(&width.to_be_bytes());
+        fctl.extend_from_slice(&height.to_be_bytes());
+        fctl.extend_from_slice(&[0; 8]);
+        fctl.extend_from_slice(&(delay.min(u16::MAX.into()) as u16).to_be_bytes());
+        fctl.extend_from_slice(&1000u16.to_be_bytes());
+        // Frames cover the whole canvas, so they replace the previous one without blending
+        fctl.extend_from_slice(&[0, 0]);
+        write_png_chunk(writer, b"fcTL", &fctl).map_err(encode_error)?;
+        sequence += 1;
+
+        if index == 0 {
+            write_png_chunk(writer, b"IDAT", &data).map_err(encode_error)?;
+        } else {
+            let mut fdat = Vec::with_capacity(4 + data.len());
+            fdat.extend_from_slice(&sequence.to_be_bytes());
+            fdat.extend_from_slice(&data);
+            write_png_chunk(writer, b"fdAT", &fdat).map_err(encode_error)?;
+            sequence += 1;
+        }
+        Ok(sequence)
+    }
+
+    /// Writes the last sprite, lasting until the end, and completes the file. Returns the
+    /// writer, which nothing was written to if no sprites were added.
+    pub fn finish(mut self, end: MediaTime) -> Result<W, Error> {
+        if let Some((start, image)) = self.pending.take() {
+            self.write_frame(&image, self.delay(start, end))?;
+        }
+        if let Some(writer) = self.writer.take() {
+            return Ok(writer);
+        }
+        let mut writer = match self.output.take() {
+            Some(Output::Gif(encoder)) => encoder.into_inner().map_err(encode_error)?,
+            Some(Output::WebP { mut writer, start }) => {
+                let end = writer.stream_position().map_err(encode_error)?;
+                let size = u32::try_from(end - start - 8)
+                    .map_err(|_| encode_error("animation exceeds the WebP size limit"))?;
+                writer
+                    .seek(SeekFrom::Start(start + 4))
+                    .and_then(|_| writer.write_all(&size.to_le_bytes()))
+                    .and_then(|_| writer.seek(SeekFrom::Start(end)))
+                    .map_err(encode_error)?;
+                writer
+            }
+            Some(Output::Apng {
+                mut writer,
+                actl,
+                frames,
+                ..
+            }) => {
+                write_png_chunk(&mut writer, b"IEND", &[]).map_err(encode_error)?;
+                let end = writer.stream_position().map_err(encode_error)?;
+                // Frame count followed by the play count, 0 loops infinitely
+                let mut control = [0; 8];
+                control[..4].copy_from_slice(&frames.to_be_bytes());
+                writer
+                    .seek(SeekFrom::Start(actl))
+                    .and_then(|_| write_png_chunk(&mut writer, b"acTL", &control))
+                    .and_then(|_| writer.seek(SeekFrom::Start(end)))
+                    .map_err(encode_error)?;
+                writer
+            }
+            None => bail!("Could not encode animation, writing its header failed"),
+        };
+        writer.flush().map_err(encode_error)?;
+        Ok(writer)
+    }
+}
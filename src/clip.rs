@@ -0,0 +1,283 @@
+use std::path::Path;
+
+use anyhow::{Error, format_err};
+use ffmpeg_api::api::*;
+use ffmpeg_api::enums::*;
+use fraction::Fraction;
+use media_time::MediaTime;
+
+/// Encoders in order of preference, with the options keeping the clips small
+type EncoderChoice = (&'static str, &'static [(&'static str, &'static str)]);
+
+const MP4_ENCODERS: &[EncoderChoice] = &[
+    ("libx264", &[("preset", "veryfast"), ("crf", "28")]),
+    ("libopenh264", &[]),
+    ("mpeg4", &[("qscale", "8")]),
+];
+
+const WEBM_ENCODERS: &[EncoderChoice] = &[
+    (
+        "libvpx-vp9",
+        &[("deadline", "realtime"), ("cpu-used", "8"), ("crf", "40")],
+    ),
+    (
+        "libvpx",
+        &[("deadline", "realtime"), ("cpu-used", "8"), ("crf", "30")],
+    ),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ClipFormat {
+    /// H.264 in MP4
+    Mp4,
+    /// VP9 or VP8 in WebM
+    WebM,
+}
+
+impl ClipFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ClipFormat::Mp4 => "mp4",
+            ClipFormat::WebM => "webm",
+        }
+    }
+
+    fn encoders(&self) -> &'static [EncoderChoice] {
+        match self {
+            ClipFormat::Mp4 => MP4_ENCODERS,
+            ClipFormat::WebM => WEBM_ENCODERS,
+        }
+    }
+}
+
+/// Short video made of snippets spread evenly across the input
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PreviewClip {
+    pub format: ClipFormat,
+    pub snippets: u32,
+    pub snippet_duration: MediaTime,
+    /// Length of the longer side of the clip in pixels
+    pub max_size: u32,
+}
+
+/// Encodes snippets into a single stream with continuous timestamps in milliseconds
+pub struct ClipEncoder {
+    encoder: AVCodecContext,
+    output: AVOutputContext,
+    stream_index: i32,
+    packet: AVPacket,
+    last_pts: Option<i64>,
+}
+
+impl ClipEncoder {
+    pub fn new(
+        format: ClipFormat,
+        output_file: &Path,
+        width: i32,
+        height: i32,
+    ) -> Result<ClipEncoder, Error> {
+        let (name, codec, options) = format
+            .encoders()
+            .iter()
+            .find_map(|(name, options)| {
+                AVCodec::find_encoder(name)
+                    .ok()
+                    .map(|codec| (*name, codec, *options))
+            })
+            .ok_or_else(|| {
+                format_err!("Could not find an encoder for {} clips", format.extension())
+            })?;
+
+        let mut output = AVOutputContext::new(output_file)
+            .map_err(|error| format_err!("Could not create clip output: {}", error))?;
+        let mut encoder = AVCodecContext::new(&codec)
+            .map_err(|error| format_err!("Could not init encoder {}: {}", name, error))?;
+        encoder.set_encoder_parameters(
+            width,
+            height,
+            AVPixelFormat::YUV420P,
+            &Fraction::new(1u64, 1000u64),
+        );
+        encoder.set_gop_size(60);
+        if output.needs_global_header() {
+            encoder.set_global_header();
+        }
+        for (key, value) in options {
+            encoder
+                .set_option(key, value)
+                .map_err(|error| format_err!("Could not configure encoder {}: {}", name, error))?;
+        }
+        encoder
+            .open_encoder(&codec)
+            .map_err(|error| format_err!("Could not open encoder {}: {}", name, error))?;
+
+        let stream_index = output
+            .add_stream(&encoder)
+            .map_err(|error| format_err!("Could not add clip stream: {}", error))?;
+        output
+            .write_header()
+            .map_err(|error| format_err!("Could not write clip header: {}", error))?;
+
+        Ok(ClipEncoder {
+            encoder,
+            output,
+            stream_index,
+            packet: AVPacket::new()
+                .map_err(|error| format_err!("Could not init clip packet: {}", error))?,
+            last_pts: None,
+        })
+    }
+
+    fn write_packets(&mut self) -> Result<(), Error> {
+        while self
+            .encoder
+            .out_packet(&mut self.packet)
+            .map_err(|error| format_err!("Could not encode clip: {}", error))?
+        {
+            self.output
+                .write_packet(
+                    &mut self.packet,
+                    self.stream_index,
+                    &self.encoder.time_base(),
+                )
+                .map_err(|error| format_err!("Could not write clip: {}", error))?;
+        }
+        Ok(())
+    }
+
+    pub fn add_frame(&mut self, frame: &mut AVFrame, pts: i64) -> Result<(), Error> {
+        // Frames closer than a millisecond can't be told apart in the clip's time base
+        if self.last_pts.is_some_and(|last_pts| pts <= last_pts) {
+            return Ok(());
+        }
+        self.last_pts = Some(pts);
+        frame.set_pts(pts);
+        self.encoder
+            .in_frame(Some(frame))
+            .map_err(|error| format_err!("Could not encode clip: {}", error))?;
+        self.write_packets()
+    }
+
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.encoder
+            .in_frame(None)
+            .map_err(|error| format_err!("Could not flush clip encoder: {}", error))?;
+        self.write_packets()?;
+        self.output
+            .write_trailer()
+            .map_err(|error| format_err!("Could not write clip trailer: {}", error))
+    }
+}
+
+/// Decodes snippets at evenly spaced positions of the input and splices them into a clip
+pub fn extract_clip(
+    input_file: &Path,
+    output_file: &Path,
+    clip: &PreviewClip,
+    duration: MediaTime,
+    scaler: SwsScaler,
+    flags: SwsFlags,
+) -> Result<(), Error> {
+    let mut avformat_context = AVFormatContext::new()?;
+    avformat_context.open_input(input_file)?;
+
+    let stream: AVStream = avformat_context
+        .streams()
+        .find(|stream| {
+            if let Ok(codec_parameters) = stream.codec_parameters() {
+                return codec_parameters.codec_type() == AVMediaType::Video;
+            }
+
+            false
+        })
+        .ok_or_else(|| format_err!("Could not find video stream"))?;
+    let index = stream.index();
+    let time_base = stream.time_base();
+
+    let codec_parameters = stream.codec_parameters()?;
+    let local_codec = codec_parameters.find_decoder()?;
+    let mut codec_context = AVCodecContext::new(&local_codec)
+        .map_err(|error| format_err!("Could not init codec context: {}", error))?;
+    codec_context.set_parameters(&codec_parameters);
+    codec_context.open(&local_codec);
+
+    let mut packet = AVPacket::new()
+        .map_err(|error| format_err!("Could not init temporary packet: {}", error))?;
+    let mut frame =
+        AVFrame::new().map_err(|error| format_err!("Could not create input frame: {}", error))?;
+    let mut output_frame =
+        AVFrame::new().map_err(|error| format_err!("Could not create clip frame: {}", error))?;
+    let mut scale_context = SwsContext::new();
+    let mut encoder: Option<ClipEncoder> = None;
+
+    let snippet_millis = clip.snippet_duration.milliseconds() as i64;
+    for snippet in 0..clip.snippets as i64 {
+        // Snippets are centered in equal parts of the input
+        let center =
+            duration.milliseconds() as i64 * (2 * snippet + 1) / (2 * clip.snippets as i64);
+        let start = MediaTime::from_millis((center - snippet_millis / 2).max(0));
+        let end = start + clip.snippet_duration;
+        let offset = snippet * snippet_millis;
+
+        avformat_context.seek(start)?;
+        codec_context.flush();
+
+        'snippet: while avformat_context.read_frame(&mut packet).is_ok() {
+            if packet.stream_index() != index {
+                continue;
+            }
+            codec_context
+                .in_packet(&mut packet)
+                .map_err(|error| format_err!("Could not load packet: {}", error))?;
+            while codec_context.out_frame(&mut frame).is_ok() {
+                let Some(pts) = frame.presentation_timestamp() else {
+                    continue;
+                };
+                let timestamp = MediaTime::from_rational(pts, &time_base)?;
+                if timestamp < start {
+                    continue;
+                }
+                if timestamp >= end {
+                    break 'snippet;
+                }
+
+                let encoder = match &mut encoder {
+                    Some(encoder) => encoder,
+                    None => {
+                        let (width, height) =
+                            clip_size(frame.width(), frame.height(), clip.max_size as i32);
+                        output_frame
+                            .init(width, height, AVPixelFormat::YUV420P)
+                            .map_err(|error| format_err!("Could not init clip frame: {}", error))?;
+                        encoder.insert(ClipEncoder::new(clip.format, output_file, width, height)?)
+                    }
+                };
+
+                scale_context
+                    .reinit(&frame, &output_frame, scaler, flags)
+                    .map_err(|error| format_err!("Could not reinit scale context: {}", error))?;
+                scale_context.scale(&frame, &mut output_frame);
+                let pts = offset + (timestamp - start).milliseconds() as i64;
+                encoder.add_frame(&mut output_frame, pts)?;
+            }
+        }
+    }
+
+    match encoder {
+        Some(encoder) => encoder.finish(),
+        None => Err(format_err!(
+            "Could not decode any frames for the preview clip"
+        )),
+    }
+}
+
+/// Scales the longer side down to the max size, keeping both sides even as required by 4:2:0
+/// chroma subsampling
+fn clip_size(width: i32, height: i32, max_size: i32) -> (i32, i32) {
+    let (width, height) = if width >= height {
+        (max_size, max_size * height / width)
+    } else {
+        (max_size * width / height, max_size)
+    };
+    ((width / 2 * 2).max(2), (height / 2 * 2).max(2))
+}
@@ -0,0 +1,192 @@
+use std::path::Path;
+
+use anyhow::{Error, format_err};
+use image::{Rgb, RgbImage};
+use media_time::MediaTime;
+
+use crate::font;
+use crate::spritesheet::ImageFormat;
+
+const MARGIN: u32 = 16;
+const GAP: u32 = 8;
+const HEADER_SCALE: u32 = 2;
+const LINE_SPACING: u32 = 4;
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const TEXT_COLOR: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Printable overview of an input with a header describing it
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ContactSheet {
+    pub columns: u32,
+    pub rows: u32,
+    pub format: ImageFormat,
+}
+
+/// Properties of the input listed in the header of the contact sheet
+pub struct MediaInfo {
+    pub file_name: String,
+    pub file_size: u64,
+    pub duration: MediaTime,
+    pub width: i32,
+    pub height: i32,
+    /// Codecs of all streams, without duplicates
+    pub codecs: Vec<String>,
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    match unit {
+        "B" => format!("{} B", bytes),
+        unit => format!("{:.1} {} ({} bytes)", size, unit, bytes),
+    }
+}
+
+impl MediaInfo {
+    fn header_lines(&self) -> Vec<String> {
+        vec![
+            format!("File: {}", self.file_name),
+            format!("Size: {}", format_size(self.file_size)),
+            format!("Duration: {}", self.duration),
+            format!("Resolution: {}x{}", self.width, self.height),
+            format!("Codecs: {}", self.codecs.join(", ")),
+        ]
+    }
+}
+
+/// Picks sprites at evenly spaced positions of the input for the contact sheet
+pub struct ContactSheetBuilder {
+    options: ContactSheet,
+    duration: MediaTime,
+    /// Start of the input, the first offered sprite if unknown
+    start: Option<MediaTime>,
+    next_slot: u32,
+    frames: Vec<(MediaTime, RgbImage)>,
+}
+
+impl ContactSheetBuilder {
+    pub fn new(
+        options: ContactSheet,
+        duration: MediaTime,
+        start: Option<MediaTime>,
+    ) -> ContactSheetBuilder {
+        ContactSheetBuilder {
+            options,
+            duration,
+            start,
+            next_slot: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    fn slots(&self) -> u32 {
+        self.options.columns * self.options.rows
+    }
+
+    /// Slots are centered in equal parts of the input
+    fn slot_time(&self, start: MediaTime, slot: u32) -> MediaTime {
+        start + self.duration * (2 * slot + 1) / (2 * self.slots())
+    }
+
+    /// Keeps the sprite if it is the first one at or after the next free slot. Sprites further
+    /// apart than the slots fill several of them, so the sheet ends up with fewer frames.
+    pub fn offer(&mut self, timestamp: MediaTime, image: &RgbImage) {
+        let start = *self.start.get_or_insert(timestamp);
+        if self.next_slot >= self.slots() || timestamp < self.slot_time(start, self.next_slot) {
+            return;
+        }
+        self.frames.push((timestamp, image.clone()));
+        while self.next_slot < self.slots() && self.slot_time(start, self.next_slot) <= timestamp {
+            self.next_slot += 1;
+        }
+    }
+
+    /// Timestamps of the kept sprites
+    pub(crate) fn timestamps(&self) -> Vec<MediaTime> {
+        self.frames.iter().map(|(timestamp, _)| *timestamp).collect()
+    }
+
+    pub fn render(&self, info: &MediaInfo) -> RgbImage {
+        let (frame_width, frame_height) = self
+            .frames
+            .first()
+            .map(|(_, image)| image.dimensions())
+            .unwrap_or((0, 0));
+        let columns = self.options.columns.min(self.frames.len() as u32).max(1);
+        let rows = (self.frames.len() as u32).div_ceil(columns);
+        let caption_height = font::text_height(1) + GAP;
+        let line_height = font::text_height(HEADER_SCALE) + LINE_SPACING;
+
+        let header = info.header_lines();
+        let header_width = header
+            .iter()
+            .map(|line| font::text_width(line, HEADER_SCALE))
+            .max()
+            .unwrap_or(0);
+        let header_height = header.len() as u32 * line_height + GAP;
+        let grid_width = columns * (frame_width + GAP) - GAP;
+        let grid_height = (rows * (frame_height + caption_height + GAP)).saturating_sub(GAP);
+
+        let mut sheet = RgbImage::from_pixel(
+            grid_width.max(header_width) + 2 * MARGIN,
+            header_height + grid_height + 2 * MARGIN,
+            BACKGROUND,
+        );
+
+        for (index, line) in header.iter().enumerate() {
+            let y = MARGIN + index as u32 * line_height;
+            font::draw_text(
+                &mut sheet,
+                MARGIN.into(),
+                y.into(),
+                line,
+                HEADER_SCALE,
+                TEXT_COLOR,
+            );
+        }
+
+        for (index, (timestamp, image)) in self.frames.iter().enumerate() {
+            let column = index as u32 % columns;
+            let row = index as u32 / columns;
+            let x = MARGIN + column * (frame_width + GAP);
+            let y = MARGIN + header_height + row * (frame_height + caption_height + GAP);
+            image::imageops::overlay(&mut sheet, image, x.into(), y.into());
+
+            let caption = timestamp.to_string();
+            let caption_x = x + frame_width.saturating_sub(font::text_width(&caption, 1)) / 2;
+            let caption_y = y + frame_height + GAP / 2;
+            font::draw_text(
+                &mut sheet,
+                caption_x.into(),
+                caption_y.into(),
+                &caption,
+                1,
+                TEXT_COLOR,
+            );
+        }
+
+        sheet
+    }
+
+    pub fn save(&self, info: &MediaInfo, path: &Path) -> Result<(), Error> {
+        if self.frames.is_empty() {
+            println!("Skipping contact sheet, no frames were sampled");
+            return Ok(());
+        }
+        let mut data = Vec::new();
+        self.options
+            .format
+            .encode(&self.render(info), &mut data)
+            .map_err(|err| format_err!("Could not encode contact sheet: {}", err))?;
+        std::fs::write(path, data)
+            .map_err(|err| format_err!("Could not write contact sheet: {}", err))
+    }
+}
@@ -0,0 +1,84 @@
+/// Average luma up to which a row or column counts as black, matching FFmpeg's cropdetect
+const BLACK_LIMIT: u32 = 24;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl std::fmt::Display for CropRect {
+    /// Formats the rectangle in the syntax of FFmpeg's crop filter
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+pub struct CropDetector {
+    width: u32,
+    height: u32,
+    /// Union of the non-black areas of all analyzed frames as left, top, right and bottom edges
+    bounds: Option<(u32, u32, u32, u32)>,
+}
+
+impl CropDetector {
+    pub fn new(width: u32, height: u32) -> CropDetector {
+        CropDetector {
+            width,
+            height,
+            bounds: None,
+        }
+    }
+
+    /// Analyzes an 8-bit luma plane of the detector's size. Entirely black frames are ignored.
+    pub fn analyze(&mut self, luma: &[u8], stride: usize) {
+        let width = self.width as usize;
+        let height = self.height as usize;
+
+        let row_is_black = |y: usize| {
+            let row = &luma[y * stride..y * stride + width];
+            row.iter().map(|&value| value as u32).sum::<u32>() <= BLACK_LIMIT * width as u32
+        };
+        let top = match (0..height).find(|&y| !row_is_black(y)) {
+            Some(top) => top,
+            None => return,
+        };
+        let bottom = (top..height).rfind(|&y| !row_is_black(y)).unwrap_or(top) + 1;
+
+        let rows = (bottom - top) as u32;
+        let column_is_black = |x: usize| {
+            (top..bottom).map(|y| luma[y * stride + x] as u32).sum::<u32>() <= BLACK_LIMIT * rows
+        };
+        let left = (0..width).find(|&x| !column_is_black(x)).unwrap_or(0);
+        let right = (left..width).rfind(|&x| !column_is_black(x)).unwrap_or(left) + 1;
+
+        let (left, top, right, bottom) = (left as u32, top as u32, right as u32, bottom as u32);
+        self.bounds = Some(match self.bounds {
+            Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+            None => (left, top, right, bottom),
+        });
+    }
+
+    /// Returns the detected crop rectangle, aligned to even coordinates for chroma subsampling,
+    /// or None if no black bars were found
+    pub fn result(&self) -> Option<CropRect> {
+        let (left, top, right, bottom) = self.bounds?;
+        let x = left & !1;
+        let y = top & !1;
+        let width = ((right - x + 1) & !1).min(self.width - x);
+        let height = ((bottom - y + 1) & !1).min(self.height - y);
+
+        if width == self.width && height == self.height {
+            None
+        } else {
+            Some(CropRect {
+                x,
+                y,
+                width,
+                height,
+            })
+        }
+    }
+}
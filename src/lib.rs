@@ -1,33 +1,170 @@
 #![allow(dead_code)]
 
+#[cfg(test)]
+mod tests;
+
+pub mod animation;
+pub mod clip;
+pub mod contactsheet;
+pub mod cropdetect;
+pub mod font;
+pub mod manifest;
+pub mod naming;
+pub mod overlay;
+pub mod scene;
 pub mod spritesheet;
+pub mod tonemap;
 mod options;
 
 use std::path::Path;
 
-use anyhow::format_err;
+use anyhow::{bail, format_err};
+use fraction::Fraction;
+use media_time::MediaTime;
 use ffmpeg_api::api::*;
 use ffmpeg_api::enums::*;
-pub use options::ExtractOptions;
+pub use options::{Deinterlace, ExtractOptions, FrameInterval, Rendition, ThumbnailNaming};
+
+fn source_colorspace(frame: &AVFrame) -> AVColorSpace {
+    match frame.colorspace() {
+        // Untagged content follows the usual player heuristic of BT.709 for HD and BT.601 for SD
+        AVColorSpace::Unspecified if frame.height() >= 720 => AVColorSpace::BT709,
+        AVColorSpace::Unspecified => AVColorSpace::SMPTE170M,
+        colorspace => colorspace,
+    }
+}
+
+/// Spritesheet output of one rendition, with its own scaler and output frame
+struct RenditionOutput {
+    manager: spritesheet::SpritesheetManager,
+    output_frame: AVFrame,
+    scale_context: SwsContext,
+    tone_mapper: Option<tonemap::ToneMapper>,
+}
+
+impl RenditionOutput {
+    fn new(
+        options: ExtractOptions,
+        output_folder: &Path,
+        name: &str,
+    ) -> anyhow::Result<RenditionOutput> {
+        Ok(RenditionOutput {
+            manager: spritesheet::SpritesheetManager::new(options, output_folder, name),
+            output_frame: AVFrame::new()
+                .map_err(|error| format_err!("Could not create output frame: {}", error))?,
+            scale_context: SwsContext::new(),
+            tone_mapper: None,
+        })
+    }
+}
+
+const CROP_DETECT_SAMPLES: i64 = 10;
+
+/// Time to wait for a followed input to grow before reading again
+const FOLLOW_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Samples frames evenly across the input and returns the smallest rectangle containing the
+/// non-black area of all of them. Rewinds the input afterwards.
+fn detect_crop(
+    avformat_context: &mut AVFormatContext,
+    codec_context: &mut AVCodecContext,
+    index: i32,
+    duration: MediaTime,
+) -> anyhow::Result<Option<cropdetect::CropRect>> {
+    let mut packet = AVPacket::new()
+        .map_err(|error| format_err!("Could not init temporary packet: {}", error))?;
+    let mut frame = AVFrame::new()
+        .map_err(|error| format_err!("Could not create input frame: {}", error))?;
+    let mut luma_frame = AVFrame::new()
+        .map_err(|error| format_err!("Could not create luma frame: {}", error))?;
+    let mut luma_context = SwsContext::new();
+    let mut detector: Option<cropdetect::CropDetector> = None;
+
+    for sample in 1..=CROP_DETECT_SAMPLES {
+        avformat_context.seek(MediaTime::from_millis(
+            duration.milliseconds() as i64 * sample / (CROP_DETECT_SAMPLES + 1),
+        ))?;
+        codec_context.flush();
+
+        while avformat_context.read_frame(&mut packet).is_ok() {
+            if packet.stream_index() != index {
+                continue;
+            }
+            codec_context
+                .in_packet(&mut packet)
+                .map_err(|error| format_err!("Could not load packet: {}", error))?;
+            if codec_context.out_frame(&mut frame).is_err() {
+                continue;
+            }
+
+            let detector = match &mut detector {
+                Some(detector) => detector,
+                None => {
+                    luma_frame
+                        .init(frame.width(), frame.height(), AVPixelFormat::GRAY8)
+                        .map_err(|error| format_err!("Could not init luma frame: {}", error))?;
+                    detector.insert(cropdetect::CropDetector::new(
+                        frame.width() as u32,
+                        frame.height() as u32,
+                    ))
+                }
+            };
+            luma_context
+                .reinit(&frame, &luma_frame, SwsScaler::Point, SwsFlags::empty())
+                .map_err(|error| format_err!("Could not reinit luma context: {}", error))?;
+            luma_context.scale(&frame, &mut luma_frame);
+            detector.analyze(luma_frame.data(0), luma_frame.linesize()[0] as usize);
+            break;
+        }
+    }
+
+    avformat_context.seek(MediaTime::from_millis(0))?;
+    codec_context.flush();
+
+    Ok(detector.and_then(|detector| detector.result()))
+}
+
+/// Passes all frames the filter graph has ready on. Returns true once the graph has ended the
+/// stream, so no more frames have to be pushed into it.
+fn drain_filter_graph(
+    graph: &mut AVFilterGraph,
+    frame: &mut AVFrame,
+    mut process_frame: impl FnMut(&mut AVFrame, &Fraction) -> anyhow::Result<()>,
+) -> anyhow::Result<bool> {
+    loop {
+        match graph
+            .pull(frame)
+            .map_err(|error| format_err!("Could not filter frame: {}", error))?
+        {
+            AVFilterStatus::Frame => process_frame(frame, &graph.time_base())?,
+            AVFilterStatus::NeedsInput => return Ok(false),
+            AVFilterStatus::EndOfStream => return Ok(true),
+        }
+    }
+}
 
 #[allow(clippy::too_many_arguments)]
 pub fn extract(
     input_file: &Path,
     output_folder: &Path,
-    options: ExtractOptions,
+    mut options: ExtractOptions,
     scaler: SwsScaler,
     flags: SwsFlags,
 ) -> anyhow::Result<()> {
+    // DASH describes the tile grid once for all sheets of a representation
+    if options.trim_last_sheet && options.dash {
+        bail!("Could not trim the last spritesheet, DASH manifests require a uniform tile grid");
+    }
+    if options.follow.is_some() && options.preview_clip.is_some() {
+        bail!("Could not place preview clip snippets, input is followed while growing");
+    }
+    if options.resume && options.animation.is_some() {
+        bail!("Could not resume the animated preview, its frames aren't kept between runs");
+    }
+
     let mut avformat_context = AVFormatContext::new()?;
     avformat_context.open_input(input_file)?;
-    let duration = avformat_context.duration()?;
-
-    std::fs::create_dir_all(output_folder)?;
-    let mut spritesheet_manager = spritesheet::SpritesheetManager::new(
-        options,
-        output_folder,
-        "preview",
-    );
+    let format_duration = avformat_context.duration()?;
 
     let mut stream: AVStream = avformat_context
         .streams()
@@ -39,10 +176,25 @@ pub fn extract(
             false
         })
         .ok_or_else(|| format_err!("Could not find video stream"))?;
-    stream.set_discard(AVDiscard::NonKey);
+
+    // Counting frames only makes sense if every frame is decoded, and a fixed number of sprites
+    // may be spaced closer than the keyframes
+    let keyframes_only = !options.all_frames
+        && options.sprite_count.is_none()
+        && !matches!(options.frame_interval, FrameInterval::Frames(_));
+    if keyframes_only {
+        stream.set_discard(AVDiscard::NonKey);
+    }
 
     let index = stream.index();
     let time_base = stream.time_base();
+    let start_time = stream.start_time()?;
+
+    // Raw streams, pipes and growing recordings may not report a duration at all
+    let duration = [format_duration, stream.duration()?]
+        .into_iter()
+        .flatten()
+        .find(|duration| duration.milliseconds() > 0);
 
     let codec_parameters = stream.codec_parameters()?;
     let local_codec = codec_parameters.find_decoder()?;
@@ -54,8 +206,133 @@ pub fn extract(
         local_codec.name()?
     );
 
-    let mut output_frame =
-        AVFrame::new().map_err(|error| format_err!("Could not create output frame: {}", error))?;
+    if let Some(sprite_count) = options.sprite_count {
+        if sprite_count == 0 {
+            bail!("Could not fit sprites, sprite count has to be at least 1");
+        }
+        if options.follow.is_some() {
+            bail!("Could not fit {} sprites, input is followed while growing", sprite_count);
+        }
+        let duration = duration.ok_or_else(|| {
+            format_err!("Could not fit {} sprites, duration of input is unknown", sprite_count)
+        })?;
+        options.frame_interval = FrameInterval::Time(duration / sprite_count);
+    }
+
+    let mut contact_sheet = match options.contact_sheet {
+        None => None,
+        Some(_) if options.follow.is_some() => {
+            bail!("Could not lay out the contact sheet, input is followed while growing")
+        }
+        Some(contact_sheet) => {
+            let duration = duration.ok_or_else(|| {
+                format_err!("Could not lay out the contact sheet, duration of input is unknown")
+            })?;
+            let mut codecs: Vec<String> = Vec::new();
+            for stream in avformat_context.streams() {
+                if let Ok(parameters) = stream.codec_parameters()
+                    && let Ok(codec) = parameters.find_decoder()
+                    && let Ok(name) = codec.name()
+                    && !codecs.contains(&name)
+                {
+                    codecs.push(name);
+                }
+            }
+            let info = contactsheet::MediaInfo {
+                file_name: input_file
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                file_size: std::fs::metadata(input_file)?.len(),
+                duration,
+                width: codec_parameters.width(),
+                height: codec_parameters.height(),
+                codecs,
+            };
+            let builder =
+                contactsheet::ContactSheetBuilder::new(contact_sheet, duration, start_time);
+            Some((builder, info))
+        }
+    };
+
+    std::fs::create_dir_all(output_folder)?;
+    let tone_mapping = options.tone_mapping;
+    let color_matrix = options.color_matrix;
+    let color_range = options.color_range;
+    let deinterlace = options.deinterlace;
+    let video_filter = options.video_filter.clone();
+    let crop_detect = options.crop_detect;
+    let follow = options.follow;
+    let resume = options.resume;
+    let preview_clip = options.preview_clip;
+    let contact_sheet_format = options.contact_sheet.map(|contact_sheet| contact_sheet.format);
+
+    let stem = input_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let render = |template: &str, max_size: u32| {
+        naming::render_template(template, |key, _| match key {
+            "stem" => Some(stem.clone()),
+            "rendition" => Some(max_size.to_string()),
+            _ => None,
+        })
+    };
+
+    let mut names = vec![render(
+        options.name_template.as_deref().unwrap_or("preview"),
+        options.max_size,
+    )];
+    let mut renditions = Vec::with_capacity(options.renditions.len());
+    for rendition in &options.renditions {
+        let name = render(
+            options.name_template.as_deref().unwrap_or("preview-{rendition}"),
+            rendition.max_size,
+        );
+        if names.contains(&name) {
+            bail!("Could not add rendition {}, name {} is used twice", rendition.max_size, name);
+        }
+        names.push(name);
+
+        let mut rendition_options = options.clone();
+        rendition_options.max_size = rendition.max_size;
+        rendition_options.num_horizontal = rendition.num_horizontal;
+        rendition_options.num_vertical = rendition.num_vertical;
+        rendition_options.renditions.clear();
+        renditions.push(rendition_options);
+    }
+    renditions.insert(0, options);
+
+    let contact_sheet_file = contact_sheet_format
+        .map(|format| output_folder.join(format!("{}_contact.{}", names[0], format.extension())));
+    let clip_file = preview_clip
+        .map(|clip| output_folder.join(format!("{}.{}", names[0], clip.format.extension())));
+
+    // Metadata, BIF, hash and manifest files are named after the distinct names, but a sheet
+    // template without `{name}` or `{rendition}` makes renditions overwrite each other's sheets
+    let mut sheet_names = Vec::with_capacity(renditions.len());
+    let mut outputs = Vec::with_capacity(renditions.len());
+    for (name, mut options) in names.into_iter().zip(renditions) {
+        options.sheet_template = options
+            .sheet_template
+            .map(|template| render(&template, options.max_size));
+        if let Some(template) = &options.sheet_template {
+            let sheet_name = naming::render_template(template, |key, _| {
+                (key == "name").then(|| name.clone())
+            });
+            if sheet_names.contains(&sheet_name) {
+                bail!(
+                    "Could not add rendition {}, spritesheet name {} is used twice",
+                    options.max_size,
+                    sheet_name
+                );
+            }
+            sheet_names.push(sheet_name);
+        }
+        let mut output = RenditionOutput::new(options, output_folder, &name)?;
+        output.manager.set_start_time(start_time);
+        outputs.push(output);
+    }
 
     if codec_parameters.codec_type() == AVMediaType::Video {
         let mut codec_context = AVCodecContext::new(&local_codec)
@@ -63,9 +340,41 @@ pub fn extract(
         codec_context.set_parameters(&codec_parameters);
         codec_context.open(&local_codec);
 
-        codec_context.set_skip_loop_filter(AVDiscard::NonKey);
-        codec_context.set_skip_idct(AVDiscard::NonKey);
-        codec_context.set_skip_frame(AVDiscard::NonKey);
+        if keyframes_only {
+            codec_context.set_skip_loop_filter(AVDiscard::NonKey);
+            codec_context.set_skip_idct(AVDiscard::NonKey);
+            codec_context.set_skip_frame(AVDiscard::NonKey);
+        }
+
+        let crop = match duration {
+            _ if !crop_detect => None,
+            Some(duration) => {
+                detect_crop(&mut avformat_context, &mut codec_context, index, duration)?
+            }
+            None => {
+                println!("Skipping crop detection, duration of input is unknown");
+                None
+            }
+        };
+        if let Some(crop) = crop {
+            println!("Detected crop: {}", crop);
+            for output in &mut outputs {
+                output.manager.add_note(format!("crop={}", crop));
+            }
+        }
+
+        if resume {
+            // Renditions ahead of the earliest one skip frames until they catch up
+            let mut timestamps = Vec::with_capacity(outputs.len());
+            for output in &mut outputs {
+                timestamps.push(output.manager.resume()?);
+            }
+            if let Some(timestamp) = timestamps.into_iter().min().flatten() {
+                println!("Resuming previous extraction at {}", timestamp);
+                avformat_context.seek(timestamp)?;
+                codec_context.flush();
+            }
+        }
 
         let mut packet = AVPacket::new()
             .map_err(|error| format_err!("Could not init temporary packet: {}", error))?;
@@ -73,61 +382,228 @@ pub fn extract(
         let mut frame = AVFrame::new()
             .map_err(|error| format_err!("Could not create input frame: {}", error))?;
 
-        let mut scale_context = SwsContext::new();
+        let mut filtered_frame = AVFrame::new()
+            .map_err(|error| format_err!("Could not create filtered frame: {}", error))?;
+        let mut filter_graph: Option<AVFilterGraph> = None;
+
+        let mut process_frame = |frame: &mut AVFrame, time_base: &Fraction| -> anyhow::Result<()> {
+            let Some(pts) = frame.presentation_timestamp() else {
+                println!("Skipping frame {} without timestamp", frame.coded_picture_number());
+                return Ok(());
+            };
+            let timestamp = media_time::MediaTime::from_rational(pts, time_base)?;
+
+            println!(
+                "Frame {}: {} @ {}",
+                frame.coded_picture_number(),
+                timestamp,
+                frame.key_frame()
+            );
+
+            // Every rendition counts every frame, even if an earlier one already needs it
+            let due = outputs
+                .iter_mut()
+                .map(|output| output.manager.fulfils_frame_interval(timestamp))
+                .collect::<Vec<bool>>();
+            if !due.contains(&true) {
+                return Ok(());
+            }
+
+            // Keeping only the temporally first field removes combing at the cost of
+            // vertical resolution, which the sprites don't have to begin with
+            let interlaced = match deinterlace {
+                Deinterlace::Disabled => false,
+                Deinterlace::Auto => frame.interlaced_frame(),
+                Deinterlace::Force => true,
+            };
+            if interlaced {
+                frame.select_field(!frame.top_field_first());
+            }
+
+            for (index, (output, due)) in outputs.iter_mut().zip(due).enumerate() {
+                if !due {
+                    continue;
+                }
+
+                if !output.manager.initialized() {
+                    output
+                        .manager
+                        .initialize(frame.width() as u32, frame.height() as u32);
+                    output.tone_mapper = tonemap::ToneMapper::new(
+                        tone_mapping,
+                        frame.color_trc(),
+                        frame.color_primaries(),
+                    );
+                    output
+                        .output_frame
+                        .init(
+                            output.manager.sprite_width() as i32,
+                            output.manager.sprite_height() as i32,
+                            match output.tone_mapper {
+                                Some(_) => AVPixelFormat::RGB48LE,
+                                None => AVPixelFormat::RGB24,
+                            },
+                        )
+                        .map_err(|error| format_err!("Could not init output frame: {}", error))?;
+                }
+
+                output
+                    .scale_context
+                    .reinit(frame, &output.output_frame, scaler, flags)
+                    .map_err(|error| format_err!("Could not reinit scale context: {}", error))?;
+                output
+                    .scale_context
+                    .set_colorspace_details(
+                        color_matrix.unwrap_or_else(|| source_colorspace(frame)),
+                        color_range.unwrap_or_else(|| frame.color_range()),
+                    )
+                    .map_err(|error| format_err!("Could not set colorspace details: {}", error))?;
+
+                output.scale_context.scale(frame, &mut output.output_frame);
+
+                let output_frame = &output.output_frame;
+                let image = match &output.tone_mapper {
+                    Some(tone_mapper) => tone_mapper.apply(
+                        output_frame.data(0),
+                        output_frame.width() as u32,
+                        output_frame.height() as u32,
+                    ),
+                    None => image::ImageBuffer::from_raw(
+                        output_frame.width() as u32,
+                        output_frame.height() as u32,
+                        output_frame.data(0).to_vec(),
+                    )
+                    .ok_or_else(|| format_err!("Could not process frame"))?,
+                };
+
+                if output.manager.fulfils_scene_change(timestamp, &image) {
+                    // The contact sheet samples the sprites of the first rendition
+                    if index == 0
+                        && let Some((builder, _)) = &mut contact_sheet
+                    {
+                        builder.offer(timestamp, &image);
+                    }
+                    output.manager.add_image(timestamp, image)?;
+                }
+            }
+
+            Ok(())
+        };
+
+        let mut last_read = std::time::Instant::now();
+        'read: loop {
+            if let Err(error) = avformat_context.read_frame(&mut packet) {
+                // Only the end of the input may be temporary, other errors won't go away by waiting
+                if !error.is_end_of_file() {
+                    bail!("Could not read frame: {}", error);
+                }
+                match follow {
+                    Some(timeout) if last_read.elapsed() < timeout => {
+                        std::thread::sleep(FOLLOW_POLL_INTERVAL);
+                        avformat_context.clear_eof().map_err(|error| {
+                            format_err!("Could not continue reading input: {}", error)
+                        })?;
+                        continue;
+                    }
+                    _ => break,
+                }
+            }
+            last_read = std::time::Instant::now();
 
-        while avformat_context.read_frame(&mut packet).is_ok() {
             if packet.stream_index() == index {
                 codec_context
                     .in_packet(&mut packet)
                     .map_err(|error| format_err!("Could not load packet: {}", error))?;
                 while codec_context.out_frame(&mut frame).is_ok() {
-                    let timestamp = media_time::MediaTime::from_rational(frame.pts(), &time_base)?;
+                    if let Some(crop) = crop {
+                        frame
+                            .crop(
+                                crop.x as i32,
+                                crop.y as i32,
+                                crop.width as i32,
+                                crop.height as i32,
+                            )
+                            .map_err(|error| format_err!("Could not crop frame: {}", error))?;
+                    }
 
-                    println!(
-                        "Frame {}: {} @ {}",
-                        frame.coded_picture_number(),
-                        timestamp,
-                        frame.key_frame()
-                    );
+                    let Some(description) = &video_filter else {
+                        process_frame(&mut frame, &time_base)?;
+                        continue;
+                    };
 
-                    if spritesheet_manager.fulfils_frame_interval(timestamp) {
-                        if !spritesheet_manager.initialized() {
-                            spritesheet_manager
-                                .initialize(frame.width() as u32, frame.height() as u32);
-                            output_frame
-                                .init(
-                                    spritesheet_manager.sprite_width() as i32,
-                                    spritesheet_manager.sprite_height() as i32,
-                                    AVPixelFormat::RGB24,
-                                )
-                                .map_err(|error| {
-                                    format_err!("Could not init output frame: {}", error)
-                                })?;
-                            scale_context
-                                .reinit(&frame, &output_frame, scaler, flags)
-                                .map_err(|error| {
-                                    format_err!("Could not reinit scale context: {}", error)
-                                })?;
-                        }
-
-                        scale_context.scale(&frame, &mut output_frame);
-
-                        spritesheet_manager.add_image(
-                            timestamp,
-                            image::ImageBuffer::from_raw(
-                                output_frame.width() as u32,
-                                output_frame.height() as u32,
-                                output_frame.data(0).to_vec(),
-                            )
-                            .ok_or_else(|| format_err!("Could not process frame"))?,
-                        )?;
+                    // The buffer source only takes frames of the size and format it was set up
+                    // for, so the graph is replaced once the stream changes them, after passing
+                    // on the frames the old one still holds
+                    if let Some(graph) = &mut filter_graph
+                        && !graph.accepts(&frame)
+                    {
+                        println!(
+                            "Frame parameters changed to {}x{} {:?}, rebuilding filter graph",
+                            frame.width(),
+                            frame.height(),
+                            frame.format()
+                        );
+                        graph.flush().map_err(|error| {
+                            format_err!("Could not flush filter graph: {}", error)
+                        })?;
+                        drain_filter_graph(graph, &mut filtered_frame, &mut process_frame)?;
+                        filter_graph = None;
+                    }
+                    let graph = match &mut filter_graph {
+                        Some(graph) => graph,
+                        None => filter_graph.insert(
+                            AVFilterGraph::new(description, &frame, &time_base).map_err(
+                                |error| format_err!("Could not init filter graph: {}", error),
+                            )?,
+                        ),
+                    };
+                    graph
+                        .push(&mut frame)
+                        .map_err(|error| format_err!("Could not filter frame: {}", error))?;
+                    if drain_filter_graph(graph, &mut filtered_frame, &mut process_frame)? {
+                        println!("Filter graph ended the stream");
+                        filter_graph = None;
+                        break 'read;
                     }
                 }
             }
         }
 
-        spritesheet_manager.end_frame(duration);
-        spritesheet_manager.save()?;
+        if let Some(graph) = filter_graph.as_mut() {
+            graph
+                .flush()
+                .map_err(|error| format_err!("Could not flush filter graph: {}", error))?;
+            drain_filter_graph(graph, &mut filtered_frame, &mut process_frame)?;
+        }
+
+        if duration.is_none() {
+            println!("Duration of input is unknown, estimating it from the last frame");
+        }
+        for output in &mut outputs {
+            let manager = &mut output.manager;
+            let end = match duration {
+                // The duration of a followed input is only known up to the moment it was opened
+                Some(_) if follow.is_some() => manager.estimated_end(),
+                Some(duration) => duration,
+                None => {
+                    manager.add_note("duration unknown");
+                    manager.estimated_end()
+                }
+            };
+            manager.end_frame(end);
+            manager.save()?;
+        }
+    }
+
+    if let (Some((builder, info)), Some(path)) = (&contact_sheet, &contact_sheet_file) {
+        builder.save(info, path)?;
+    }
+
+    if let (Some(clip), Some(clip_file)) = (&preview_clip, &clip_file) {
+        let duration = duration.ok_or_else(|| {
+            format_err!("Could not place preview clip snippets, duration of input is unknown")
+        })?;
+        clip::extract_clip(input_file, clip_file, clip, duration, scaler, flags)?;
     }
 
     Ok(())
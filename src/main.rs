@@ -1,10 +1,21 @@
 use std::path::Path;
+use std::time::Duration;
 
-use ffmpeg_api::enums::{SwsFlags, SwsScaler};
-use image::ImageFormat as ImageOutputFormat;
+use ffmpeg_api::enums::{AVColorRange, AVColorSpace, SwsFlags, SwsScaler};
+use image::codecs::png::{CompressionType, FilterType};
+use image::{Rgb, Rgba};
+use jpeg_encoder::SamplingFactor;
 use media_time::MediaTime;
+use perceptual_hash::HashAlgorithm;
 use structopt::StructOpt;
-use media_ingestion::ExtractOptions;
+use media_ingestion::{Deinterlace, ExtractOptions, FrameInterval, Rendition, ThumbnailNaming};
+use media_ingestion::animation::{Animation, AnimationFormat, FrameDelay};
+use media_ingestion::clip::{ClipFormat, PreviewClip};
+use media_ingestion::contactsheet::ContactSheet;
+use media_ingestion::overlay::{OverlayPosition, TimestampOverlay};
+use media_ingestion::scene::SceneDetection;
+use media_ingestion::spritesheet::ImageFormat;
+use media_ingestion::tonemap::ToneMapping;
 
 fn parse_scaler(src: &str) -> Result<SwsScaler, String> {
     match src {
@@ -23,23 +34,379 @@ fn parse_scaler(src: &str) -> Result<SwsScaler, String> {
     }
 }
 
+/// Parses a non-negative duration in milliseconds with the suffix `ms`, or in seconds with an
+/// optional suffix `s`
+fn parse_time(src: &str) -> Option<MediaTime> {
+    if let Some(millis) = src.strip_suffix("ms") {
+        return millis
+            .parse::<i64>()
+            .ok()
+            .filter(|millis| *millis >= 0)
+            .map(MediaTime::from_millis);
+    }
+    src.strip_suffix('s')
+        .unwrap_or(src)
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| *seconds >= 0.0)
+        .and_then(MediaTime::checked_from_seconds_f64)
+}
+
+fn parse_frame_interval(src: &str) -> Result<FrameInterval, String> {
+    if let Some(frames) = src.strip_suffix('f') {
+        return match frames.parse::<u32>() {
+            Ok(frames) if frames > 0 => Ok(FrameInterval::Frames(frames)),
+            _ => Err(format!("Invalid frame interval: {}", src)),
+        };
+    }
+    // Tiny intervals like 1e-12 round down to nothing
+    match parse_time(src) {
+        Some(interval) if !interval.is_zero() => Ok(FrameInterval::Time(interval)),
+        _ => Err(format!("Invalid frame interval: {}", src)),
+    }
+}
+
+fn parse_scene_interval(src: &str) -> Result<MediaTime, String> {
+    parse_time(src).ok_or_else(|| format!("Invalid scene interval: {}", src))
+}
+
+fn parse_sprite_count(src: &str) -> Result<u32, String> {
+    match src.parse::<u32>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("Invalid sprite count: {}", src)),
+    }
+}
+
+fn parse_hash_algorithm(src: &str) -> Result<HashAlgorithm, String> {
+    match src {
+        "dhash" => Ok(HashAlgorithm::DHash),
+        "phash" => Ok(HashAlgorithm::PHash),
+        _ => Err(format!("Invalid hash algorithm: {}", src)),
+    }
+}
+
+fn parse_animation_format(src: &str) -> Result<AnimationFormat, String> {
+    match src {
+        "gif" => Ok(AnimationFormat::Gif),
+        "webp" => Ok(AnimationFormat::WebP),
+        "apng" => Ok(AnimationFormat::Apng),
+        _ => Err(format!("Invalid animation format: {}", src)),
+    }
+}
+
+/// Parses playback speeds like `10x` or fixed frame delays like `200ms` and `0.2s`
+fn parse_frame_delay(src: &str) -> Result<FrameDelay, String> {
+    let invalid = || format!("Invalid frame delay: {}", src);
+    if let Some(speed) = src.strip_suffix('x') {
+        return match speed.parse::<f64>() {
+            Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(FrameDelay::Speed(speed)),
+            _ => Err(invalid()),
+        };
+    }
+    match parse_frame_interval(src) {
+        Ok(FrameInterval::Time(delay)) => Ok(FrameDelay::Fixed(delay)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_clip_format(src: &str) -> Result<ClipFormat, String> {
+    match src {
+        "mp4" => Ok(ClipFormat::Mp4),
+        "webm" => Ok(ClipFormat::WebM),
+        _ => Err(format!("Invalid clip format: {}", src)),
+    }
+}
+
+fn parse_snippet_duration(src: &str) -> Result<MediaTime, String> {
+    match parse_frame_interval(src) {
+        Ok(FrameInterval::Time(duration)) => Ok(duration),
+        _ => Err(format!("Invalid snippet duration: {}", src)),
+    }
+}
+
+fn parse_rendition(src: &str) -> Result<Rendition, String> {
+    let invalid = || format!("Invalid rendition: {}", src);
+    let (max_size, grid) = src.split_once(':').unwrap_or((src, "5x5"));
+    let (num_horizontal, num_vertical) = grid.split_once('x').ok_or_else(invalid)?;
+    let rendition = Rendition {
+        max_size: max_size.parse().map_err(|_| invalid())?,
+        num_horizontal: num_horizontal.parse().map_err(|_| invalid())?,
+        num_vertical: num_vertical.parse().map_err(|_| invalid())?,
+    };
+    if rendition.max_size == 0 || rendition.num_horizontal == 0 || rendition.num_vertical == 0 {
+        return Err(invalid());
+    }
+    Ok(rendition)
+}
+
+fn parse_grid(src: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid grid: {}", src);
+    let (columns, rows) = src.split_once('x').ok_or_else(invalid)?;
+    match (columns.parse::<u32>(), rows.parse::<u32>()) {
+        (Ok(columns), Ok(rows)) if columns > 0 && rows > 0 => Ok((columns, rows)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_contact_sheet_format(src: &str) -> Result<ImageFormat, String> {
+    match src {
+        "jpeg" | "jpg" => Ok(ImageFormat::Jpeg(90, false, SamplingFactor::R_4_2_0)),
+        "png" => Ok(ImageFormat::Png(CompressionType::Default, FilterType::Adaptive)),
+        _ => Err(format!("Invalid contact sheet format: {}", src)),
+    }
+}
+
+fn parse_thumbnail_naming(src: &str) -> Result<ThumbnailNaming, String> {
+    match src {
+        "index" => Ok(ThumbnailNaming::Index),
+        "timestamp" => Ok(ThumbnailNaming::Timestamp),
+        _ => Err(format!("Invalid thumbnail naming: {}", src)),
+    }
+}
+
+/// Spritesheet format, its encoder settings are taken from the format specific options
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum SheetFormat {
+    Jpeg,
+    Png,
+    Bmp,
+    WebP,
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+fn parse_sheet_format(src: &str) -> Result<SheetFormat, String> {
+    match src {
+        "jpeg" | "jpg" => Ok(SheetFormat::Jpeg),
+        "png" => Ok(SheetFormat::Png),
+        "bmp" => Ok(SheetFormat::Bmp),
+        "webp" => Ok(SheetFormat::WebP),
+        #[cfg(feature = "avif")]
+        "avif" => Ok(SheetFormat::Avif),
+        #[cfg(not(feature = "avif"))]
+        "avif" => Err(String::from("Invalid format: avif requires building with --features avif")),
+        _ => Err(format!("Invalid format: {}", src)),
+    }
+}
+
+fn parse_quality(src: &str) -> Result<u8, String> {
+    match src.parse::<u8>() {
+        Ok(quality) if (1..=100).contains(&quality) => Ok(quality),
+        _ => Err(format!("Invalid quality: {}", src)),
+    }
+}
+
+fn parse_jpeg_subsampling(src: &str) -> Result<SamplingFactor, String> {
+    match src {
+        "444" => Ok(SamplingFactor::R_4_4_4),
+        "422" => Ok(SamplingFactor::R_4_2_2),
+        "420" => Ok(SamplingFactor::R_4_2_0),
+        _ => Err(format!("Invalid jpeg subsampling: {}", src)),
+    }
+}
+
+fn parse_png_compression(src: &str) -> Result<CompressionType, String> {
+    match src {
+        "fast" => Ok(CompressionType::Fast),
+        "default" => Ok(CompressionType::Default),
+        "best" => Ok(CompressionType::Best),
+        _ => Err(format!("Invalid png compression: {}", src)),
+    }
+}
+
+fn parse_png_filter(src: &str) -> Result<FilterType, String> {
+    match src {
+        "none" => Ok(FilterType::NoFilter),
+        "sub" => Ok(FilterType::Sub),
+        "up" => Ok(FilterType::Up),
+        "avg" => Ok(FilterType::Avg),
+        "paeth" => Ok(FilterType::Paeth),
+        "adaptive" => Ok(FilterType::Adaptive),
+        _ => Err(format!("Invalid png filter: {}", src)),
+    }
+}
+
+fn parse_overlay_position(src: &str) -> Result<OverlayPosition, String> {
+    match src {
+        "top-left" => Ok(OverlayPosition::TopLeft),
+        "top-right" => Ok(OverlayPosition::TopRight),
+        "bottom-left" => Ok(OverlayPosition::BottomLeft),
+        "bottom-right" => Ok(OverlayPosition::BottomRight),
+        _ => Err(format!("Invalid overlay position: {}", src)),
+    }
+}
+
+/// Parses colors as `#rrggbb` or `#rrggbbaa`, with `none` being fully transparent
+fn parse_color(src: &str) -> Result<Rgba<u8>, String> {
+    let invalid = || format!("Invalid color: {}", src);
+    if src == "none" {
+        return Ok(Rgba([0, 0, 0, 0]));
+    }
+    let hex = src.strip_prefix('#').ok_or_else(invalid)?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut color = Rgba([0, 0, 0, 255]);
+    for (index, channel) in color.0.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(color)
+}
+
+fn parse_opaque_color(src: &str) -> Result<Rgb<u8>, String> {
+    match parse_color(src)? {
+        Rgba([r, g, b, 255]) => Ok(Rgb([r, g, b])),
+        _ => Err(format!("Invalid opaque color: {}", src)),
+    }
+}
+
+fn parse_tone_mapping(src: &str) -> Result<ToneMapping, String> {
+    match src {
+        "none" => Ok(ToneMapping::Disabled),
+        "clip" => Ok(ToneMapping::Clip),
+        "reinhard" => Ok(ToneMapping::Reinhard),
+        "hable" => Ok(ToneMapping::Hable),
+        "bt2390" => Ok(ToneMapping::Bt2390),
+        _ => Err(format!("Invalid tone mapping: {}", src)),
+    }
+}
+
+fn parse_color_matrix(src: &str) -> Result<AVColorSpace, String> {
+    match src {
+        "bt601" => Ok(AVColorSpace::SMPTE170M),
+        "bt709" => Ok(AVColorSpace::BT709),
+        "bt2020" => Ok(AVColorSpace::BT2020NCL),
+        "fcc" => Ok(AVColorSpace::FCC),
+        "smpte240m" => Ok(AVColorSpace::SMPTE240M),
+        _ => Err(format!("Invalid color matrix: {}", src)),
+    }
+}
+
+fn parse_color_range(src: &str) -> Result<AVColorRange, String> {
+    match src {
+        "limited" | "tv" => Ok(AVColorRange::MPEG),
+        "full" | "pc" => Ok(AVColorRange::JPEG),
+        _ => Err(format!("Invalid color range: {}", src)),
+    }
+}
+
+fn parse_deinterlace(src: &str) -> Result<Deinterlace, String> {
+    match src {
+        "off" => Ok(Deinterlace::Disabled),
+        "auto" => Ok(Deinterlace::Auto),
+        "force" => Ok(Deinterlace::Force),
+        _ => Err(format!("Invalid deinterlace mode: {}", src)),
+    }
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(author, about)]
 struct Options {
     input: String,
     output: String,
-    #[structopt(long = "frame-interval", default_value = "2")]
-    frame_interval: i64,
+    #[structopt(long = "frame-interval", default_value = "2", parse(try_from_str = parse_frame_interval))]
+    frame_interval: FrameInterval,
+    #[structopt(long = "sprite-count", parse(try_from_str = parse_sprite_count))]
+    sprite_count: Option<u32>,
     #[structopt(long = "num-horizontal", default_value = "5")]
     num_horizontal: u32,
     #[structopt(long = "num-vertical", default_value = "5")]
     num_vertical: u32,
+    #[structopt(long = "rendition", parse(try_from_str = parse_rendition))]
+    renditions: Vec<Rendition>,
+    #[structopt(long = "thumbnail-naming", default_value = "index", parse(try_from_str = parse_thumbnail_naming))]
+    thumbnail_naming: ThumbnailNaming,
+    #[structopt(long = "name")]
+    name_template: Option<String>,
+    #[structopt(long = "sheet-name")]
+    sheet_template: Option<String>,
+    #[structopt(long = "tile-padding", default_value = "0")]
+    tile_padding: u32,
+    #[structopt(long = "background", default_value = "#000000", parse(try_from_str = parse_opaque_color))]
+    background: Rgb<u8>,
+    #[structopt(long = "trim-last-sheet")]
+    trim_last_sheet: bool,
+    #[structopt(long = "base-url")]
+    base_url: Option<String>,
     #[structopt(long = "max-size", default_value = "240")]
     max_size: u32,
-    #[structopt(long = "format", default_value = "jpg")]
-    format: String,
+    #[structopt(long = "format", default_value = "jpg", parse(try_from_str = parse_sheet_format))]
+    format: SheetFormat,
+    #[structopt(long = "webp-lossless")]
+    webp_lossless: bool,
+    #[structopt(long = "bif")]
+    bif: bool,
+    #[structopt(long = "hls")]
+    hls: bool,
+    #[structopt(long = "dash")]
+    dash: bool,
+    #[structopt(long = "animation", parse(try_from_str = parse_animation_format))]
+    animation: Option<AnimationFormat>,
+    #[structopt(long = "animation-speed", default_value = "10x", parse(try_from_str = parse_frame_delay))]
+    animation_speed: FrameDelay,
+    #[structopt(long = "preview-clip", parse(try_from_str = parse_clip_format))]
+    preview_clip: Option<ClipFormat>,
+    #[structopt(long = "clip-snippets", default_value = "6")]
+    clip_snippets: u32,
+    #[structopt(long = "clip-snippet-duration", default_value = "1.5", parse(try_from_str = parse_snippet_duration))]
+    clip_snippet_duration: MediaTime,
+    #[structopt(long = "clip-max-size", default_value = "320")]
+    clip_max_size: u32,
+    #[structopt(long = "contact-sheet", parse(try_from_str = parse_grid))]
+    contact_sheet: Option<(u32, u32)>,
+    #[structopt(long = "contact-sheet-format", default_value = "png", parse(try_from_str = parse_contact_sheet_format))]
+    contact_sheet_format: ImageFormat,
+    #[structopt(long = "perceptual-hash", parse(try_from_str = parse_hash_algorithm))]
+    perceptual_hash: Option<HashAlgorithm>,
+    #[structopt(long = "timestamp-overlay", parse(try_from_str = parse_overlay_position))]
+    timestamp_overlay: Option<OverlayPosition>,
+    #[structopt(long = "overlay-color", default_value = "#ffffff", parse(try_from_str = parse_opaque_color))]
+    overlay_color: Rgb<u8>,
+    #[structopt(long = "overlay-background", default_value = "#000000a0", parse(try_from_str = parse_color))]
+    overlay_background: Rgba<u8>,
+    #[structopt(long = "max-sheet-size")]
+    max_sheet_size: Option<u64>,
+    #[structopt(long = "quality", parse(try_from_str = parse_quality))]
+    quality: Option<u8>,
+    #[structopt(long = "jpeg-progressive")]
+    jpeg_progressive: bool,
+    #[structopt(long = "jpeg-subsampling", default_value = "420", parse(try_from_str = parse_jpeg_subsampling))]
+    jpeg_subsampling: SamplingFactor,
+    #[structopt(long = "png-compression", default_value = "default", parse(try_from_str = parse_png_compression))]
+    png_compression: CompressionType,
+    #[structopt(long = "png-filter", default_value = "adaptive", parse(try_from_str = parse_png_filter))]
+    png_filter: FilterType,
+    #[cfg(feature = "avif")]
+    #[structopt(long = "avif-speed", default_value = "4")]
+    avif_speed: u8,
+    #[structopt(long = "all-frames")]
+    all_frames: bool,
     #[structopt(long = "scaler", default_value = "area", parse(try_from_str = parse_scaler))]
     scaler: SwsScaler,
+    #[structopt(long = "tone-mapping", default_value = "hable", parse(try_from_str = parse_tone_mapping))]
+    tone_mapping: ToneMapping,
+    #[structopt(long = "color-matrix", parse(try_from_str = parse_color_matrix))]
+    color_matrix: Option<AVColorSpace>,
+    #[structopt(long = "color-range", parse(try_from_str = parse_color_range))]
+    color_range: Option<AVColorRange>,
+    #[structopt(long = "deinterlace", default_value = "auto", parse(try_from_str = parse_deinterlace))]
+    deinterlace: Deinterlace,
+    #[structopt(long = "video-filter")]
+    video_filter: Option<String>,
+    #[structopt(long = "crop-detect")]
+    crop_detect: bool,
+    #[structopt(long = "scene-threshold")]
+    scene_threshold: Option<f64>,
+    #[structopt(long = "scene-min-interval", default_value = "1", parse(try_from_str = parse_scene_interval))]
+    scene_min_interval: MediaTime,
+    #[structopt(long = "scene-max-interval", default_value = "60", parse(try_from_str = parse_scene_interval))]
+    scene_max_interval: MediaTime,
+    #[structopt(long = "follow")]
+    follow: bool,
+    #[structopt(long = "follow-timeout", default_value = "30")]
+    follow_timeout: u64,
+    #[structopt(long = "resume")]
+    resume: bool,
     #[structopt(long = "fast-chroma")]
     fast_chroma: bool,
     #[structopt(long = "fast-rounding")]
@@ -69,13 +436,72 @@ fn main() -> anyhow::Result<()> {
             max_size: options.max_size,
             num_horizontal: options.num_horizontal,
             num_vertical: options.num_vertical,
-            frame_interval: MediaTime::from_seconds(options.frame_interval),
-            format: match options.format.as_str() {
-                "jpeg" | "jpg" => ImageOutputFormat::Jpeg,
-                "png" => ImageOutputFormat::Png,
-                "bmp" => ImageOutputFormat::Bmp,
-                _ => panic!("Unsupported image format: {}", options.format),
-            }
+            renditions: options.renditions,
+            thumbnail_naming: options.thumbnail_naming,
+            name_template: options.name_template,
+            sheet_template: options.sheet_template,
+            tile_padding: options.tile_padding,
+            background: options.background,
+            trim_last_sheet: options.trim_last_sheet,
+            base_url: options.base_url,
+            frame_interval: options.frame_interval,
+            sprite_count: options.sprite_count,
+            format: match options.format {
+                SheetFormat::Jpeg => ImageFormat::Jpeg(
+                    options.quality.unwrap_or(75),
+                    options.jpeg_progressive,
+                    options.jpeg_subsampling,
+                ),
+                SheetFormat::Png => ImageFormat::Png(options.png_compression, options.png_filter),
+                SheetFormat::Bmp => ImageFormat::Bmp,
+                SheetFormat::WebP if options.webp_lossless => ImageFormat::WebP(None),
+                SheetFormat::WebP => ImageFormat::WebP(Some(options.quality.unwrap_or(75))),
+                #[cfg(feature = "avif")]
+                SheetFormat::Avif => {
+                    ImageFormat::Avif(options.quality.unwrap_or(80), options.avif_speed)
+                }
+            },
+            bif: options.bif,
+            hls: options.hls,
+            dash: options.dash,
+            animation: options.animation.map(|format| Animation {
+                format,
+                frame_delay: options.animation_speed,
+            }),
+            preview_clip: options.preview_clip.map(|format| PreviewClip {
+                format,
+                snippets: options.clip_snippets,
+                snippet_duration: options.clip_snippet_duration,
+                max_size: options.clip_max_size,
+            }),
+            contact_sheet: options.contact_sheet.map(|(columns, rows)| ContactSheet {
+                columns,
+                rows,
+                format: options.contact_sheet_format,
+            }),
+            perceptual_hash: options.perceptual_hash,
+            timestamp_overlay: options.timestamp_overlay.map(|position| TimestampOverlay {
+                position,
+                color: options.overlay_color,
+                background: Some(options.overlay_background).filter(|color| color[3] != 0),
+            }),
+            max_sheet_size: options.max_sheet_size,
+            all_frames: options.all_frames,
+            tone_mapping: options.tone_mapping,
+            color_matrix: options.color_matrix,
+            color_range: options.color_range,
+            deinterlace: options.deinterlace,
+            video_filter: options.video_filter,
+            crop_detect: options.crop_detect,
+            scene_detection: options.scene_threshold.map(|threshold| SceneDetection {
+                threshold,
+                min_interval: options.scene_min_interval,
+                max_interval: options.scene_max_interval,
+            }),
+            follow: options
+                .follow
+                .then(|| Duration::from_secs(options.follow_timeout)),
+            resume: options.resume,
         },
         options.scaler,
         flags,
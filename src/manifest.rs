@@ -0,0 +1,184 @@
+use media_time::MediaTime;
+
+/// A spritesheet as listed in a thumbnail track
+pub struct TileSheet {
+    pub uri: String,
+    pub start: MediaTime,
+    pub duration: MediaTime,
+    /// Number of sprites on the sheet, the last sheet may not be full
+    pub tiles: u32,
+    /// Grid of the sheet, which is smaller than the track's for a trimmed last sheet
+    pub columns: u32,
+    pub rows: u32,
+    /// Size of the encoded sheet in bytes
+    pub size: u64,
+}
+
+/// Tiling information of a spritesheet output, shared by DASH and HLS thumbnail tracks
+pub struct ThumbnailTrack {
+    /// Segment template of the sheet file names, using `$Number$` or `$Time$` in milliseconds
+    pub media: String,
+    pub mime_type: &'static str,
+    pub codec: &'static str,
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub sheets: Vec<TileSheet>,
+}
+
+/// Formats a time as fractional seconds with millisecond precision
+fn seconds(time: MediaTime) -> String {
+    let millis = time.milliseconds();
+    format!("{}.{:03}", millis / 1000, millis % 1000)
+}
+
+/// Escapes text for use in a double quoted XML attribute
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl ThumbnailTrack {
+    fn duration(&self) -> MediaTime {
+        self.sheets
+            .last()
+            .map(|sheet| sheet.start + sheet.duration)
+            .unwrap_or_else(|| MediaTime::from_millis(0))
+    }
+
+    /// Average bitrate of the sheets in bits per second, as required by both manifests
+    fn bandwidth(&self) -> u64 {
+        let bytes: u64 = self.sheets.iter().map(|sheet| sheet.size).sum();
+        let millis = self.duration().milliseconds().max(1) as u64;
+        (bytes * 8 * 1000).div_ceil(millis)
+    }
+
+    fn resolution(&self) -> String {
+        format!(
+            "{}x{}",
+            self.tile_width * self.columns,
+            self.tile_height * self.rows
+        )
+    }
+
+    /// Image media playlist following the HLS image playlist extension. Playlists of extractions
+    /// still in progress are written as event playlists without end tag.
+    pub fn hls_media_playlist(&self, complete: bool) -> String {
+        let target_duration = self
+            .sheets
+            .iter()
+            .map(|sheet| (sheet.duration.milliseconds() as u64).div_ceil(1000))
+            .max()
+            .unwrap_or(0);
+
+        let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:7\n");
+        playlist += &format!("#EXT-X-TARGETDURATION:{}\n", target_duration);
+        playlist += "#EXT-X-MEDIA-SEQUENCE:0\n";
+        playlist += if complete {
+            "#EXT-X-PLAYLIST-TYPE:VOD\n"
+        } else {
+            "#EXT-X-PLAYLIST-TYPE:EVENT\n"
+        };
+        playlist += "#EXT-X-IMAGES-ONLY\n";
+        for sheet in &self.sheets {
+            playlist += &format!("#EXTINF:{},\n", seconds(sheet.duration));
+            playlist += &format!(
+                "#EXT-X-TILES:RESOLUTION={}x{},LAYOUT={}x{},DURATION={}\n",
+                self.tile_width,
+                self.tile_height,
+                sheet.columns,
+                sheet.rows,
+                seconds(sheet.duration / sheet.tiles.max(1)),
+            );
+            playlist += &format!("{}\n", sheet.uri);
+        }
+        if complete {
+            playlist += "#EXT-X-ENDLIST\n";
+        }
+        playlist
+    }
+
+    /// Master playlist referencing the image media playlist, to be merged into the master
+    /// playlist of the packaged stream
+    pub fn hls_master_playlist(&self, media_playlist: &str) -> String {
+        format!(
+            "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-IMAGE-STREAM-INF:BANDWIDTH={},RESOLUTION={},CODECS=\"{}\",URI=\"{}\"\n",
+            self.bandwidth(),
+            self.resolution(),
+            self.codec,
+            media_playlist,
+        )
+    }
+
+    /// Thumbnail adaptation set following the DASH-IF interoperability guidelines
+    pub fn dash_adaptation_set(&self) -> String {
+        let mut timeline = String::new();
+        let mut sheets = self.sheets.iter().peekable();
+        while let Some(sheet) = sheets.next() {
+            // Consecutive sheets of equal duration are collapsed into one entry with repetitions
+            let mut repeat = 0;
+            let mut end = sheet.start + sheet.duration;
+            while let Some(next) =
+                sheets.next_if(|next| next.start == end && next.duration == sheet.duration)
+            {
+                repeat += 1;
+                end = next.start + next.duration;
+            }
+            timeline += &format!(
+                "          <S t=\"{}\" d=\"{}\"",
+                sheet.start.milliseconds(),
+                sheet.duration.milliseconds()
+            );
+            if repeat > 0 {
+                timeline += &format!(" r=\"{}\"", repeat);
+            }
+            timeline += "/>\n";
+        }
+
+        format!(
+            concat!(
+                "    <AdaptationSet contentType=\"image\" mimeType=\"{}\">\n",
+                "      <SegmentTemplate media=\"{}\" startNumber=\"0\" timescale=\"1000\">\n",
+                "        <SegmentTimeline>\n",
+                "{}",
+                "        </SegmentTimeline>\n",
+                "      </SegmentTemplate>\n",
+                "      <Representation id=\"thumbnails_{}x{}\" bandwidth=\"{}\" width=\"{}\" height=\"{}\">\n",
+                "        <EssentialProperty schemeIdUri=\"http://dashif.org/thumbnail_tile\" value=\"{}x{}\"/>\n",
+                "      </Representation>\n",
+                "    </AdaptationSet>\n",
+            ),
+            self.mime_type,
+            xml_escape(&self.media),
+            timeline,
+            self.tile_width,
+            self.tile_height,
+            self.bandwidth(),
+            self.tile_width * self.columns,
+            self.tile_height * self.rows,
+            self.columns,
+            self.rows,
+        )
+    }
+
+    /// Static MPD containing only the thumbnail adaptation set, to be merged into the MPD of the
+    /// packaged stream
+    pub fn dash_manifest(&self) -> String {
+        format!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"urn:mpeg:dash:profile:isoff-live:2011\" ",
+                "type=\"static\" mediaPresentationDuration=\"PT{}S\" minBufferTime=\"PT2S\">\n",
+                "  <Period id=\"0\" start=\"PT0S\">\n",
+                "{}",
+                "  </Period>\n",
+                "</MPD>\n",
+            ),
+            seconds(self.duration()),
+            self.dash_adaptation_set(),
+        )
+    }
+}
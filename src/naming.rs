@@ -0,0 +1,33 @@
+/// Replaces `{key}` and `{key:spec}` placeholders in a template with the values returned by
+/// `value`. Placeholders without a value are kept, so templates can be rendered in stages.
+pub fn render_template(
+    template: &str,
+    value: impl Fn(&str, Option<&str>) -> Option<String>,
+) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[start..=start + length];
+        let (key, spec) = match placeholder[1..length].split_once(':') {
+            Some((key, spec)) => (key, Some(spec)),
+            None => (&placeholder[1..length], None),
+        };
+
+        result += &rest[..start];
+        match value(key, spec) {
+            Some(value) => result += &value,
+            None => result += placeholder,
+        }
+        rest = &rest[start + length + 1..];
+    }
+    result += rest;
+    result
+}
+
+/// Returns the width of a spec like `04`, which zero pads numbers to four digits
+pub fn padding(spec: Option<&str>) -> usize {
+    spec.and_then(|spec| spec.parse().ok()).unwrap_or(0)
+}
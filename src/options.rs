@@ -1,9 +1,108 @@
-use image::ImageFormat as ImageOutputFormat;
+use ffmpeg_api::enums::{AVColorRange, AVColorSpace};
+use perceptual_hash::HashAlgorithm;
 
+use crate::animation::Animation;
+use crate::clip::PreviewClip;
+use crate::contactsheet::ContactSheet;
+use crate::overlay::TimestampOverlay;
+use crate::scene::SceneDetection;
+use crate::spritesheet::ImageFormat;
+use crate::tonemap::ToneMapping;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FrameInterval {
+    /// Minimum time between two sprites
+    Time(media_time::MediaTime),
+    /// Number of decoded frames between two sprites
+    Frames(u32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ThumbnailNaming {
+    /// Number thumbnails consecutively
+    Index,
+    /// Name thumbnails after their timestamp in milliseconds
+    Timestamp,
+}
+
+/// Size and grid of an additional set of spritesheets
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rendition {
+    pub max_size: u32,
+    pub num_horizontal: u32,
+    pub num_vertical: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Deinterlace {
+    Disabled,
+    /// Deinterlace frames flagged as interlaced by the decoder
+    Auto,
+    /// Deinterlace all frames, for sources with missing field flags
+    Force,
+}
+
+#[derive(Clone)]
 pub struct ExtractOptions {
     pub max_size: u32,
     pub num_horizontal: u32,
     pub num_vertical: u32,
-    pub frame_interval: media_time::MediaTime,
-    pub format: ImageOutputFormat,
+    /// Additional renditions extracted in the same pass, named after their max size
+    pub renditions: Vec<Rendition>,
+    /// Naming of the individual thumbnails written instead of spritesheets for a 1×1 layout
+    pub thumbnail_naming: ThumbnailNaming,
+    /// Template for the base name of the metadata files, with the placeholders `{stem}` of the
+    /// input file and `{rendition}` for the max size
+    pub name_template: Option<String>,
+    /// Template for spritesheet file names, additionally with the placeholders `{name}`,
+    /// `{index}`, `{timestamp}` of the first sprite in milliseconds, and `{ext}`. Numbers can be
+    /// zero padded like `{index:04}`.
+    pub sheet_template: Option<String>,
+    /// Margin in pixels around each sprite within its spritesheet cell, so neighbouring sprites
+    /// don't bleed into each other when scaled
+    pub tile_padding: u32,
+    /// Color of the padding and of unused cells in the last spritesheet
+    pub background: image::Rgb<u8>,
+    /// Crop the last spritesheet to the rows it uses, or to the used columns of a single row
+    pub trim_last_sheet: bool,
+    /// Prefix for the spritesheet URLs in the WebVTT cues, e.g. to point at a CDN
+    pub base_url: Option<String>,
+    pub frame_interval: FrameInterval,
+    /// Spread this many sprites evenly across the input instead of using the frame interval
+    pub sprite_count: Option<u32>,
+    pub format: ImageFormat,
+    /// Also write all sprites into a Roku BIF archive for trick play
+    pub bif: bool,
+    /// Also write an HLS image media playlist describing the spritesheets
+    pub hls: bool,
+    /// Also write a DASH manifest with a thumbnail adaptation set describing the spritesheets
+    pub dash: bool,
+    /// Also encode all sprites as an animated preview
+    pub animation: Option<Animation>,
+    /// Also encode a short video of snippets spread across the input
+    pub preview_clip: Option<PreviewClip>,
+    /// Also render a printable contact sheet with a header describing the input
+    pub contact_sheet: Option<ContactSheet>,
+    /// Also write perceptual hashes of all sprites, to find duplicates among inputs
+    pub perceptual_hash: Option<HashAlgorithm>,
+    /// Burn the timestamp of each sprite into one of its corners
+    pub timestamp_overlay: Option<TimestampOverlay>,
+    /// Lower the quality of spritesheets exceeding this many bytes until they fit
+    pub max_sheet_size: Option<u64>,
+    /// Decode all frames instead of only keyframes, implied by frame count intervals
+    pub all_frames: bool,
+    pub tone_mapping: ToneMapping,
+    pub color_matrix: Option<AVColorSpace>,
+    pub color_range: Option<AVColorRange>,
+    pub deinterlace: Deinterlace,
+    /// Filter chain in FFmpeg's filtergraph syntax, applied to decoded frames before sprites are made
+    pub video_filter: Option<String>,
+    /// Crop black bars detected in frames sampled across the input
+    pub crop_detect: bool,
+    /// Emit sprites on scene changes instead of at a fixed frame interval
+    pub scene_detection: Option<SceneDetection>,
+    /// Keep reading a growing input until it hasn't grown for this long
+    pub follow: Option<std::time::Duration>,
+    /// Continue after the last completed spritesheet of a previous run into the same folder
+    pub resume: bool,
 }
@@ -0,0 +1,73 @@
+use image::{Rgb, RgbImage, Rgba};
+use media_time::MediaTime;
+
+use crate::font;
+
+/// Sprite height per font scale step, so the text stays legible on larger sprites
+const SCALE_STEP: u32 = 120;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OverlayPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Timestamp burned into a corner of every sprite
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimestampOverlay {
+    pub position: OverlayPosition,
+    pub color: Rgb<u8>,
+    /// Box drawn behind the text, blended with the sprite according to its alpha
+    pub background: Option<Rgba<u8>>,
+}
+
+fn blend(pixel: &mut Rgb<u8>, color: Rgba<u8>) {
+    let alpha = u32::from(color.0[3]);
+    for (channel, &value) in pixel.0.iter_mut().zip(color.0.iter()) {
+        *channel = ((u32::from(value) * alpha + u32::from(*channel) * (255 - alpha)) / 255) as u8;
+    }
+}
+
+impl TimestampOverlay {
+    pub fn draw(&self, image: &mut RgbImage, timestamp: MediaTime) {
+        let text = timestamp.to_string();
+        let scale = (image.height() / SCALE_STEP).max(1);
+        let padding = 2 * scale;
+        let box_width = font::text_width(&text, scale) + 2 * padding;
+        let box_height = font::text_height(scale) + 2 * padding;
+
+        let left = match self.position {
+            OverlayPosition::TopLeft | OverlayPosition::BottomLeft => 0,
+            OverlayPosition::TopRight | OverlayPosition::BottomRight => {
+                image.width().saturating_sub(box_width)
+            }
+        };
+        let top = match self.position {
+            OverlayPosition::TopLeft | OverlayPosition::TopRight => 0,
+            OverlayPosition::BottomLeft | OverlayPosition::BottomRight => {
+                image.height().saturating_sub(box_height)
+            }
+        };
+
+        if let Some(background) = self.background {
+            let right = (left + box_width).min(image.width());
+            let bottom = (top + box_height).min(image.height());
+            for y in top..bottom {
+                for x in left..right {
+                    blend(image.get_pixel_mut(x, y), background);
+                }
+            }
+        }
+
+        font::draw_text(
+            image,
+            (left + padding).into(),
+            (top + padding).into(),
+            &text,
+            scale,
+            self.color,
+        );
+    }
+}
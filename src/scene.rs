@@ -0,0 +1,73 @@
+use image::RgbImage;
+use media_time::MediaTime;
+
+/// Number of blocks per side of the downscaled luma grid frames are compared on
+const GRID_SIZE: u32 = 16;
+
+#[derive(Debug, Copy, Clone)]
+pub struct SceneDetection {
+    /// Mean luma difference between 0 and 1 above which a frame counts as a new scene
+    pub threshold: f64,
+    /// Minimum time between two sprites, even across scene changes
+    pub min_interval: MediaTime,
+    /// Maximum time between two sprites, even within a scene
+    pub max_interval: MediaTime,
+}
+
+pub struct SceneDetector {
+    options: SceneDetection,
+    reference: Option<Vec<f64>>,
+}
+
+/// Averages the luma of the image over a grid of blocks, which makes the comparison robust
+/// against noise and compression artifacts
+fn luma_grid(image: &RgbImage) -> Vec<f64> {
+    let mut sums = vec![0.0; (GRID_SIZE * GRID_SIZE) as usize];
+    let mut counts = vec![0u32; (GRID_SIZE * GRID_SIZE) as usize];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let block_x = x * GRID_SIZE / image.width();
+        let block_y = y * GRID_SIZE / image.height();
+        let block = (block_y * GRID_SIZE + block_x) as usize;
+        let [r, g, b] = pixel.0;
+        sums[block] += (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) / 255.0;
+        counts[block] += 1;
+    }
+    sums.iter()
+        .zip(counts.iter())
+        .map(|(&sum, &count)| if count == 0 { 0.0 } else { sum / count as f64 })
+        .collect()
+}
+
+impl SceneDetector {
+    pub fn new(options: SceneDetection) -> SceneDetector {
+        SceneDetector {
+            options,
+            reference: None,
+        }
+    }
+
+    pub fn options(&self) -> &SceneDetection {
+        &self.options
+    }
+
+    /// Compares the image against the last accepted sprite rather than the previous frame, so
+    /// gradual changes like slow pans eventually count as a new scene as well
+    pub fn is_scene_change(&self, image: &RgbImage) -> bool {
+        let reference = match &self.reference {
+            Some(reference) => reference,
+            None => return true,
+        };
+        let grid = luma_grid(image);
+        let difference = grid
+            .iter()
+            .zip(reference.iter())
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f64>()
+            / grid.len() as f64;
+        difference > self.options.threshold
+    }
+
+    pub fn accept(&mut self, image: &RgbImage) {
+        self.reference = Some(luma_grid(image));
+    }
+}
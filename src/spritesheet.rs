@@ -1,16 +1,136 @@
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 
 use anyhow::{bail, Error, format_err};
-use image::{DynamicImage, ImageFormat as ImageOutputFormat, RgbImage};
+use bif::BifFile;
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::error::{EncodingError, ImageFormatHint};
+use image::{ExtendedColorType, ImageEncoder, ImageError, ImageResult, Rgb, RgbImage};
+use jpeg_encoder::{ColorType, SamplingFactor};
 use media_time::MediaTime;
+use perceptual_hash::HashSequence;
 use webvtt::{WebVTTCue, WebVTTFile};
-use crate::options::ExtractOptions;
+use crate::animation::{Animation, AnimationEncoder};
+use crate::manifest::{ThumbnailTrack, TileSheet};
+use crate::naming::{padding, render_template};
+use crate::options::{ExtractOptions, FrameInterval, ThumbnailNaming};
+use crate::overlay::TimestampOverlay;
+use crate::scene::SceneDetector;
 
+/// JPEG quality of BIF frames if spritesheets aren't written as JPEG
+const BIF_QUALITY: u8 = 75;
+/// Compression effort of lossless WebP between 0 (fastest) and 100 (smallest)
+const WEBP_LOSSLESS_EFFORT: f32 = 75.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum ImageFormat {
-    Jpeg(i32),
-    Png,
+    /// JPEG with quality between 1 and 100, whether it is progressive and its chroma subsampling
+    Jpeg(u8, bool, SamplingFactor),
+    Png(CompressionType, FilterType),
+    Bmp,
+    /// WebP with quality between 1 and 100, or lossless if None
+    WebP(Option<u8>),
+    /// AVIF with quality between 1 and 100 and encoder speed between 1 and 10
+    #[cfg(feature = "avif")]
+    Avif(u8, u8),
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg(_, _, _) => "jpeg",
+            ImageFormat::Png(_, _) => "png",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::WebP(_) => "webp",
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif(_, _) => "avif",
+        }
+    }
+
+    /// Returns the quality setting of lossy formats
+    pub fn quality(&self) -> Option<u8> {
+        match *self {
+            ImageFormat::Jpeg(quality, _, _) => Some(quality),
+            ImageFormat::WebP(quality) => quality,
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif(quality, _) => Some(quality),
+            _ => None,
+        }
+    }
+
+    /// Returns the same format with a different quality, lossless formats are returned unchanged
+    pub fn with_quality(&self, quality: u8) -> ImageFormat {
+        match *self {
+            ImageFormat::Jpeg(_, progressive, sampling) => {
+                ImageFormat::Jpeg(quality, progressive, sampling)
+            }
+            ImageFormat::WebP(Some(_)) => ImageFormat::WebP(Some(quality)),
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif(_, speed) => ImageFormat::Avif(quality, speed),
+            format => format,
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg(_, _, _) => "image/jpeg",
+            ImageFormat::Png(_, _) => "image/png",
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::WebP(_) => "image/webp",
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif(_, _) => "image/avif",
+        }
+    }
+
+    pub fn encode(&self, image: &RgbImage, mut writer: impl Write) -> ImageResult<()> {
+        let (width, height) = image.dimensions();
+        let color = ExtendedColorType::Rgb8;
+        match *self {
+            ImageFormat::Jpeg(quality, progressive, sampling) => {
+                // jpeg-encoder, as the encoder of the image crate can't write progressive JPEG or
+                // choose the chroma subsampling
+                let error = |message: String| encoding_error(image::ImageFormat::Jpeg, message);
+                let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+                    return Err(error(format!("{}x{} exceeds the JPEG size limit", width, height)));
+                };
+                let mut encoder = jpeg_encoder::Encoder::new(&mut writer, quality);
+                encoder.set_progressive(progressive);
+                encoder.set_sampling_factor(sampling);
+                encoder
+                    .encode(image.as_raw(), width, height, ColorType::Rgb)
+                    .map_err(|err| match err {
+                        jpeg_encoder::EncodingError::IoError(err) => ImageError::IoError(err),
+                        err => error(err.to_string()),
+                    })
+            }
+            ImageFormat::Png(compression, filter) => {
+                PngEncoder::new_with_quality(&mut writer, compression, filter)
+                    .write_image(image, width, height, color)
+            }
+            ImageFormat::Bmp => BmpEncoder::new(&mut writer).write_image(image, width, height, color),
+            ImageFormat::WebP(quality) => {
+                // libwebp, as the encoder of the image crate only writes lossless images
+                let encoder = webp::Encoder::from_rgb(image.as_raw(), width, height);
+                let data = match quality {
+                    Some(quality) => encoder.encode_simple(false, quality.into()),
+                    None => encoder.encode_simple(true, WEBP_LOSSLESS_EFFORT),
+                }
+                .map_err(|err| encoding_error(image::ImageFormat::WebP, format!("{:?}", err)))?;
+                writer.write_all(&data).map_err(ImageError::IoError)
+            }
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif(quality, speed) => {
+                image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut writer, speed, quality)
+                    .write_image(image, width, height, color)
+            }
+        }
+    }
+}
+
+fn encoding_error(format: image::ImageFormat, message: String) -> ImageError {
+    ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(format), message))
 }
 
 pub struct SpritesheetManager {
@@ -19,14 +139,37 @@ pub struct SpritesheetManager {
     max_side: u32,
     sprite_width: u32,
     sprite_height: u32,
+    tile_padding: u32,
+    background: Rgb<u8>,
+    trim_last_sheet: bool,
     spritesheet: RgbImage,
     current_image: u32,
     last_timestamp: MediaTime,
-    frame_interval: MediaTime,
+    frame_interval: FrameInterval,
+    frame_count: u64,
+    last_frame: u64,
+    /// Start time of the stream, if known
+    start_time: Option<MediaTime>,
+    first_frame_timestamp: Option<MediaTime>,
+    last_frame_timestamp: MediaTime,
+    sprite_count: Option<u32>,
+    scene_detector: Option<SceneDetector>,
+    timestamp_overlay: Option<TimestampOverlay>,
     metadata: WebVTTFile,
+    bif: Option<BifFile>,
+    hashes: Option<HashSequence>,
+    animation: Option<Animation>,
+    /// Opened with the first sprite
+    animation_encoder: Option<AnimationEncoder<BufWriter<File>>>,
+    hls: bool,
+    dash: bool,
     output_path: PathBuf,
     name: String,
-    format: ImageOutputFormat,
+    sheet_template: String,
+    base_url: String,
+    sheet_start: MediaTime,
+    format: ImageFormat,
+    max_sheet_size: Option<u64>,
     initialized: bool,
 }
 
@@ -36,20 +179,54 @@ impl SpritesheetManager {
         output_path: impl Into<PathBuf>,
         name: impl AsRef<str>,
     ) -> SpritesheetManager {
+        let name = String::from(name.as_ref());
+        let individual = options.num_horizontal == 1 && options.num_vertical == 1;
+        let sheet_template = match (&options.sheet_template, options.thumbnail_naming) {
+            (Some(template), _) => template.clone(),
+            (None, ThumbnailNaming::Timestamp) if individual => {
+                String::from("{name}_{timestamp}.{ext}")
+            }
+            (None, _) => String::from("{name}_{index}.{ext}"),
+        };
+        let sheet_template = render_template(&sheet_template, |key, _| {
+            (key == "name").then(|| name.clone())
+        });
+
         SpritesheetManager {
             num_horizontal: options.num_horizontal,
             num_vertical: options.num_vertical,
             max_side: options.max_size,
             sprite_width: 0,
             sprite_height: 0,
+            tile_padding: options.tile_padding,
+            background: options.background,
+            trim_last_sheet: options.trim_last_sheet,
             spritesheet: RgbImage::new(0, 0),
             current_image: 0,
             last_timestamp: MediaTime::from_millis(0),
             frame_interval: options.frame_interval,
+            frame_count: 0,
+            last_frame: 0,
+            start_time: None,
+            first_frame_timestamp: None,
+            last_frame_timestamp: MediaTime::from_millis(0),
+            sprite_count: options.sprite_count,
+            scene_detector: options.scene_detection.map(SceneDetector::new),
+            timestamp_overlay: options.timestamp_overlay,
             metadata: WebVTTFile::new(),
+            bif: options.bif.then(BifFile::new),
+            hashes: options.perceptual_hash.map(HashSequence::new),
+            animation: options.animation,
+            animation_encoder: None,
+            hls: options.hls,
+            dash: options.dash,
             output_path: output_path.into(),
-            name: String::from(name.as_ref()),
+            name,
+            sheet_template,
+            base_url: options.base_url.unwrap_or_default(),
+            sheet_start: MediaTime::from_millis(0),
             format: options.format,
+            max_sheet_size: options.max_sheet_size,
             initialized: false,
         }
     }
@@ -62,17 +239,115 @@ impl SpritesheetManager {
             self.sprite_height = self.max_side;
             self.sprite_width = self.sprite_height * width / height;
         }
-        self.spritesheet = self.reinit_buffer();
+        if !self.individual() {
+            self.spritesheet = self.reinit_buffer();
+        }
         self.initialized = true;
     }
 
     fn reinit_buffer(&self) -> RgbImage {
-        RgbImage::new(
-            self.sprite_width * self.num_horizontal,
-            self.sprite_height * self.num_vertical,
+        RgbImage::from_pixel(
+            self.tile_width() * self.num_horizontal,
+            self.tile_height() * self.num_vertical,
+            self.background,
         )
     }
 
+    /// Width of a spritesheet cell, including the padding on both sides of the sprite
+    fn tile_width(&self) -> u32 {
+        self.sprite_width + 2 * self.tile_padding
+    }
+
+    /// Height of a spritesheet cell, including the padding on both sides of the sprite
+    fn tile_height(&self) -> u32 {
+        self.sprite_height + 2 * self.tile_padding
+    }
+
+    /// Restores the state of a previous run from its metadata, keeping all completed
+    /// spritesheets. Returns the timestamp to continue from, or None if there is nothing to resume.
+    pub fn resume(&mut self) -> Result<Option<MediaTime>, Error> {
+        let path = self.output_path.join(format!("{}.vtt", self.name));
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut metadata = WebVTTFile::load(&path)
+            .map_err(|error| format_err!("Could not read spritesheet metadata: {}", error))?;
+
+        let sprites_per_sheet = (self.num_horizontal * self.num_vertical) as usize;
+        let sprites = metadata.cues().len() / sprites_per_sheet * sprites_per_sheet;
+        metadata.truncate(sprites);
+        let kept_sheets: Vec<String> = metadata
+            .cues()
+            .iter()
+            .step_by(sprites_per_sheet)
+            .enumerate()
+            .map(|(index, cue)| self.file_name(index as u32, cue.start()))
+            .collect();
+        // The cue of the last kept sprite is recreated once the next sprite is found
+        let Some(last) = metadata.pop() else {
+            return Ok(None);
+        };
+        self.sheet_start = metadata
+            .cues()
+            .get(sprites - sprites_per_sheet)
+            .map(|cue| cue.start())
+            .unwrap_or(last.start());
+        for cue in metadata.cues() {
+            self.metadata.add(cue.clone());
+        }
+        // Size budget notes of sheets that are encoded again are replaced by the new ones
+        for note in metadata.notes() {
+            let sheet = note.split_once(" quality=").map(|(sheet, _)| sheet);
+            if sheet.is_none_or(|sheet| kept_sheets.iter().any(|kept| kept == sheet)) {
+                self.add_note(note.clone());
+            }
+        }
+
+        let bif_path = self.output_path.join(format!("{}.bif", self.name));
+        if let Some(bif) = &mut self.bif
+            && bif_path.exists()
+        {
+            *bif = BifFile::load(&bif_path)
+                .map_err(|error| format_err!("Could not read BIF archive: {}", error))?;
+            if bif.len() < sprites {
+                println!("BIF archive is missing frames of the previous extraction");
+            }
+            bif.truncate(sprites);
+        }
+
+        let hashes_path = self.output_path.join(format!("{}_hashes.json", self.name));
+        if let Some(hashes) = &mut self.hashes
+            && hashes_path.exists()
+        {
+            let loaded = HashSequence::load(&hashes_path)
+                .map_err(|error| format_err!("Could not read perceptual hashes: {}", error))?;
+            if loaded.algorithm() != hashes.algorithm() {
+                bail!("Perceptual hashes of the previous extraction use a different algorithm");
+            }
+            *hashes = loaded;
+            if hashes.len() < sprites {
+                println!("Perceptual hashes are missing frames of the previous extraction");
+            }
+            hashes.truncate(sprites);
+        }
+
+        self.current_image = sprites as u32;
+        self.last_timestamp = last.start();
+        Ok(Some(last.start()))
+    }
+
+    /// Adds a note about the extraction to the spritesheet metadata
+    pub fn add_note(&mut self, note: impl Into<String>) {
+        let note = note.into();
+        if !self.metadata.notes().contains(&note) {
+            self.metadata.add_note(note);
+        }
+    }
+
+    pub(crate) fn metadata(&self) -> &WebVTTFile {
+        &self.metadata
+    }
+
     pub fn initialized(&self) -> bool {
         self.initialized
     }
@@ -85,6 +360,37 @@ impl SpritesheetManager {
         self.sprite_height
     }
 
+    /// A 1×1 layout writes every sprite as an individual thumbnail instead of a spritesheet
+    fn individual(&self) -> bool {
+        self.num_horizontal == 1 && self.num_vertical == 1
+    }
+
+    /// Names a spritesheet, or an individual thumbnail in a 1×1 layout, after its index and the
+    /// timestamp of its first sprite
+    fn file_name(&self, index: u32, timestamp: MediaTime) -> String {
+        render_template(&self.sheet_template, |key, spec| match key {
+            "index" => Some(format!("{:0width$}", index, width = padding(spec))),
+            "timestamp" => Some(format!(
+                "{:0width$}",
+                timestamp.milliseconds(),
+                width = padding(spec)
+            )),
+            "ext" => Some(self.ending()),
+            _ => None,
+        })
+    }
+
+    /// Columns and rows of a spritesheet holding the given number of sprites, which only differ
+    /// from the configured grid for a trimmed last sheet
+    fn grid(&self, sprites: u32) -> (u32, u32) {
+        if !self.trim_last_sheet || sprites >= self.num_horizontal * self.num_vertical {
+            return (self.num_horizontal, self.num_vertical);
+        }
+        let rows = sprites.div_ceil(self.num_horizontal);
+        let columns = if rows == 1 { sprites } else { self.num_horizontal };
+        (columns, rows)
+    }
+
     fn sprite_index(&self, current: u32) -> u32 {
         current % (self.num_horizontal * self.num_vertical)
     }
@@ -95,28 +401,78 @@ impl SpritesheetManager {
 
     fn x(&self, current: u32) -> u32 {
         let index = current % self.num_horizontal;
-        index * self.sprite_width
+        index * self.tile_width() + self.tile_padding
     }
 
     fn ending(&self) -> String {
-        String::from(match self.format {
-            ImageOutputFormat::Png => "png",
-            ImageOutputFormat::Jpeg => "jpeg",
-            ImageOutputFormat::Bmp => "bmp",
-            _ => panic!("Invalid image format: {:?}", self.format),
-        })
+        String::from(self.format.extension())
     }
 
     fn y(&self, current: u32) -> u32 {
         let index = (current / self.num_horizontal) % self.num_vertical;
-        index * self.sprite_height
+        index * self.tile_height() + self.tile_padding
+    }
+
+    /// Sets the start time of the stream, from which a fixed number of sprites is spread.
+    /// Without it, sprites are spread from the first decoded frame.
+    pub fn set_start_time(&mut self, start_time: Option<MediaTime>) {
+        self.start_time = start_time;
+    }
+
+    /// Counts the decoded frame and checks whether it is due for the next sprite
+    pub fn fulfils_frame_interval(&mut self, timestamp: MediaTime) -> bool {
+        self.frame_count += 1;
+        let first_frame_timestamp = *self.first_frame_timestamp.get_or_insert(timestamp);
+        self.last_frame_timestamp = timestamp;
+
+        if let Some(scene_detector) = &self.scene_detector {
+            return self.current_image == 0
+                || timestamp - self.last_timestamp > scene_detector.options().min_interval;
+        }
+
+        match (self.frame_interval, self.sprite_count) {
+            // Sprites are scheduled at fixed positions, so late keyframes don't shift later ones
+            (FrameInterval::Time(frame_interval), Some(sprite_count)) => {
+                let start = self.start_time.unwrap_or(first_frame_timestamp);
+                self.current_image < sprite_count
+                    && timestamp - start >= frame_interval * self.current_image
+            }
+            (FrameInterval::Time(frame_interval), None) => {
+                self.current_image == 0 || timestamp - self.last_timestamp > frame_interval
+            }
+            (FrameInterval::Frames(frames), _) => {
+                self.current_image == 0 || self.frame_count - self.last_frame >= frames as u64
+            }
+        }
+    }
+
+    /// Estimates the end of an input of unknown duration as one frame interval after the last
+    /// decoded frame
+    pub fn estimated_end(&self) -> MediaTime {
+        let interval = match (self.frame_interval, self.first_frame_timestamp) {
+            (FrameInterval::Time(interval), _) => interval,
+            (FrameInterval::Frames(frames), Some(first)) if self.frame_count > 1 => {
+                (self.last_frame_timestamp - first) / (self.frame_count - 1) as u32 * frames
+            }
+            (FrameInterval::Frames(_), _) => MediaTime::from_millis(0),
+        };
+        self.last_frame_timestamp + interval
     }
 
-    pub fn fulfils_frame_interval(&self, timestamp: MediaTime) -> bool {
-        self.current_image == 0 || timestamp - self.last_timestamp > self.frame_interval
+    /// Checks whether a frame fulfilling the frame interval differs enough from the previous
+    /// sprite. Always true if scene detection is disabled.
+    pub fn fulfils_scene_change(&self, timestamp: MediaTime, image: &RgbImage) -> bool {
+        match &self.scene_detector {
+            Some(scene_detector) => {
+                self.current_image == 0
+                    || timestamp - self.last_timestamp >= scene_detector.options().max_interval
+                    || scene_detector.is_scene_change(image)
+            }
+            None => true,
+        }
     }
 
-    pub fn add_image(&mut self, timestamp: MediaTime, image: RgbImage) -> Result<(), Error> {
+    pub fn add_image(&mut self, timestamp: MediaTime, mut image: RgbImage) -> Result<(), Error> {
         if image.width() != self.sprite_width || image.height() != self.sprite_height {
             bail!(
                 "Wrong image size: {}x{}, but expected {}x{}",
@@ -127,19 +483,76 @@ impl SpritesheetManager {
             )
         }
 
-        let x: i64 = self.x(self.current_image).into();
-        let y: i64 = self.y(self.current_image).into();
-        image::imageops::overlay(&mut self.spritesheet, &image, x, y);
+        if let Some(scene_detector) = &mut self.scene_detector {
+            scene_detector.accept(&image);
+        }
+
+        // Hashed before the overlay, so identical content at different times hashes the same
+        if let Some(hashes) = &mut self.hashes {
+            hashes.add_image(timestamp, &image);
+        }
+
+        // Drawn after scene detection, so the changing text doesn't count as a scene change
+        if let Some(overlay) = &self.timestamp_overlay {
+            overlay.draw(&mut image, timestamp);
+        }
+
+        if let Some(bif) = &mut self.bif {
+            // Roku players only decode baseline JPEG frames
+            let quality = match self.format {
+                ImageFormat::Jpeg(quality, _, _) => quality,
+                _ => BIF_QUALITY,
+            };
+            let mut jpeg = Vec::new();
+            ImageFormat::Jpeg(quality, false, SamplingFactor::R_4_2_0)
+                .encode(&image, &mut jpeg)
+                .map_err(|err| format_err!("Could not encode BIF frame: {}", err))?;
+            bif.add(timestamp, jpeg);
+        }
+
+        if let Some(animation) = self.animation
+            && self.animation_encoder.is_none()
+        {
+            let name = format!("{}_animated.{}", self.name, animation.format.extension());
+            let file = File::create(self.output_path.join(&name))
+                .map_err(|error| format_err!("Could not write animation {}: {}", name, error))?;
+            self.animation_encoder = Some(AnimationEncoder::new(animation, BufWriter::new(file)));
+        }
+        if let Some(animation) = &mut self.animation_encoder {
+            animation.add(timestamp, &image)?;
+        }
+
+        if self.individual() {
+            let name = self.file_name(self.current_image, timestamp);
+            self.write_image(&name, &image)?;
+        } else {
+            let x: i64 = self.x(self.current_image).into();
+            let y: i64 = self.y(self.current_image).into();
+            image::imageops::overlay(&mut self.spritesheet, &image, x, y);
+        }
 
         if self.current_image != 0 {
             self.end_frame(timestamp);
+            // Once the first sprite of a new sheet ends the last cue of the previous one, the
+            // metadata is complete up to there and a later run can resume from it
+            if self.sprite_index(self.current_image) == 0 {
+                self.save_metadata()?;
+                self.save_bif()?;
+                self.save_hashes()?;
+                self.save_manifests(false)?;
+            }
         }
 
-        if self.sprite_index(self.current_image + 1) == 0 {
-            self.save_spritesheet()?;
+        if self.sprite_index(self.current_image) == 0 {
+            self.sheet_start = timestamp;
+        }
+
+        if !self.individual() && self.sprite_index(self.current_image + 1) == 0 {
+            self.save_spritesheet(self.num_horizontal * self.num_vertical)?;
         }
 
         self.last_timestamp = timestamp;
+        self.last_frame = self.frame_count;
         self.current_image += 1;
 
         Ok(())
@@ -152,46 +565,223 @@ impl SpritesheetManager {
             0
         };
 
-        self.metadata.add(WebVTTCue::new(
-            self.last_timestamp,
-            timestamp,
+        let payload = if self.individual() {
+            format!(
+                "{}{}",
+                self.base_url,
+                self.file_name(prev_image, self.last_timestamp)
+            )
+        } else {
             format!(
-                "{}_{}.{}#xywh={},{},{},{}",
-                self.name,
-                self.spritesheet_index(prev_image),
-                self.ending(),
+                "{}{}#xywh={},{},{},{}",
+                self.base_url,
+                self.file_name(self.spritesheet_index(prev_image), self.sheet_start),
                 self.x(prev_image),
                 self.y(prev_image),
                 self.sprite_width,
                 self.sprite_height
-            ),
-        ));
+            )
+        };
+        self.metadata.add(WebVTTCue::new(self.last_timestamp, timestamp, payload));
     }
 
-    fn save_spritesheet(&mut self) -> Result<(), Error> {
-        let name = format!(
-            "{}_{}.{}",
-            self.name,
-            self.spritesheet_index(self.current_image),
-            self.ending(),
-        );
-
-        let file = File::create(self.output_path.join(&name))
-            .map_err(|err| format_err!("Could not create spritesheet {}: {}", &name, err))?;
+    fn save_spritesheet(&mut self, sprites: u32) -> Result<(), Error> {
+        let name = self.file_name(self.spritesheet_index(self.current_image), self.sheet_start);
 
         let new_buffer = self.reinit_buffer();
-        DynamicImage::ImageRgb8(std::mem::replace(&mut self.spritesheet, new_buffer))
-            .write_to(&mut BufWriter::new(file), self.format)
-            .map_err(|err| format_err!("Could not write spritesheet {}: {}", &name, err))?;
+        let mut spritesheet = std::mem::replace(&mut self.spritesheet, new_buffer);
+        let (columns, rows) = self.grid(sprites);
+        if (columns, rows) != (self.num_horizontal, self.num_vertical) {
+            // Sprites are placed row by row from the top left, so the cue coordinates stay valid
+            spritesheet = image::imageops::crop_imm(
+                &spritesheet,
+                0,
+                0,
+                columns * self.tile_width(),
+                rows * self.tile_height(),
+            )
+            .to_image();
+        }
+        self.write_image(&name, &spritesheet)
+    }
 
+    fn write_image(&mut self, name: &str, image: &RgbImage) -> Result<(), Error> {
+        let data = self.encode_spritesheet(name, image)?;
+        let path = self.output_path.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|err| format_err!("Could not create folder for {}: {}", name, err))?;
+        }
+        std::fs::write(path, data)
+            .map_err(|err| format_err!("Could not write spritesheet {}: {}", name, err))?;
         Ok(())
     }
 
-    pub fn save(&mut self) -> Result<(), Error> {
-        self.save_spritesheet()?;
+    /// Encodes the spritesheet, lowering the quality as far as necessary to fit the size budget.
+    /// Adapted qualities are recorded in the metadata.
+    pub(crate) fn encode_spritesheet(&mut self, name: &str, spritesheet: &RgbImage) -> Result<Vec<u8>, Error> {
+        let encode = |format: ImageFormat| -> Result<Vec<u8>, Error> {
+            let mut data = Vec::new();
+            format
+                .encode(spritesheet, &mut data)
+                .map_err(|err| format_err!("Could not encode spritesheet {}: {}", name, err))?;
+            Ok(data)
+        };
+
+        let data = encode(self.format)?;
+        let Some(max_sheet_size) = self.max_sheet_size else {
+            return Ok(data);
+        };
+        if data.len() as u64 <= max_sheet_size {
+            return Ok(data);
+        }
+        let Some(quality) = self.format.quality() else {
+            println!("Spritesheet {} exceeds size budget, format is lossless", name);
+            return Ok(data);
+        };
+
+        // Search the highest quality below the configured one that still fits the budget
+        let mut best: Option<(u8, Vec<u8>)> = None;
+        let (mut low, mut high) = (1, quality - 1);
+        while low <= high {
+            let middle = (low + high) / 2;
+            let data = encode(self.format.with_quality(middle))?;
+            if data.len() as u64 <= max_sheet_size {
+                best = Some((middle, data));
+                low = middle + 1;
+            } else {
+                high = middle - 1;
+            }
+        }
+
+        let (quality, data) = match best {
+            Some(best) => best,
+            None => {
+                println!("Spritesheet {} exceeds size budget even at lowest quality", name);
+                (1, encode(self.format.with_quality(1))?)
+            }
+        };
+        self.add_note(format!("{} quality={} size={}", name, quality, data.len()));
+        Ok(data)
+    }
+
+    fn save_metadata(&self) -> Result<(), Error> {
         self.metadata
             .save(self.output_path.join(format!("{}.vtt", self.name)))
             .map_err(|error| format_err!("Could not write spritesheet metadata: {}", error))?;
         Ok(())
     }
+
+    fn save_bif(&self) -> Result<(), Error> {
+        if let Some(bif) = &self.bif {
+            bif.save(self.output_path.join(format!("{}.bif", self.name)))
+                .map_err(|error| format_err!("Could not write BIF archive: {}", error))?;
+        }
+        Ok(())
+    }
+
+    fn save_hashes(&self) -> Result<(), Error> {
+        if let Some(hashes) = &self.hashes {
+            hashes
+                .save(self.output_path.join(format!("{}_hashes.json", self.name)))
+                .map_err(|error| format_err!("Could not write perceptual hashes: {}", error))?;
+        }
+        Ok(())
+    }
+
+    fn save_animation(&mut self) -> Result<(), Error> {
+        let (Some(animation), Some(last)) =
+            (self.animation_encoder.take(), self.metadata.cues().last())
+        else {
+            return Ok(());
+        };
+        animation.finish(last.end()).map(|_| ())
+    }
+
+    /// Describes the spritesheets with a cue in the metadata, grouping the cues by sheet
+    fn thumbnail_track(&self) -> ThumbnailTrack {
+        let mut sheets: Vec<TileSheet> = Vec::new();
+        let (first_columns, first_rows) = self.grid(1);
+        for cue in self.metadata.cues() {
+            let url = cue.payload().split('#').next().unwrap_or_default();
+            let uri = url.strip_prefix(&self.base_url).unwrap_or(url);
+            match sheets.last_mut() {
+                Some(sheet) if sheet.uri == uri => {
+                    sheet.duration = cue.end() - sheet.start;
+                    sheet.tiles += 1;
+                    (sheet.columns, sheet.rows) = self.grid(sheet.tiles);
+                }
+                _ => sheets.push(TileSheet {
+                    uri: String::from(uri),
+                    start: cue.start(),
+                    duration: cue.end() - cue.start(),
+                    tiles: 1,
+                    columns: first_columns,
+                    rows: first_rows,
+                    size: std::fs::metadata(self.output_path.join(uri))
+                        .map(|metadata| metadata.len())
+                        .unwrap_or(0),
+                }),
+            }
+        }
+
+        // Sheets are numbered from zero and start at their first sprite, as DASH segments do
+        let media = render_template(&self.sheet_template, |key, spec| match (key, padding(spec)) {
+            ("index", 0) => Some(String::from("$Number$")),
+            ("index", width) => Some(format!("$Number%0{}d$", width)),
+            ("timestamp", 0) => Some(String::from("$Time$")),
+            ("timestamp", width) => Some(format!("$Time%0{}d$", width)),
+            ("ext", _) => Some(self.ending()),
+            _ => None,
+        });
+        ThumbnailTrack {
+            media,
+            mime_type: self.format.mime_type(),
+            codec: self.format.extension(),
+            columns: self.num_horizontal,
+            rows: self.num_vertical,
+            // Players split sheets evenly, so tiles include the padding around the sprites
+            tile_width: self.tile_width(),
+            tile_height: self.tile_height(),
+            sheets,
+        }
+    }
+
+    fn save_manifests(&self, complete: bool) -> Result<(), Error> {
+        if !self.hls && !self.dash {
+            return Ok(());
+        }
+        let track = self.thumbnail_track();
+
+        let mut manifests = Vec::new();
+        if self.hls {
+            let media_playlist = format!("{}.m3u8", self.name);
+            manifests.push((
+                format!("{}_master.m3u8", self.name),
+                track.hls_master_playlist(&media_playlist),
+            ));
+            manifests.push((media_playlist, track.hls_media_playlist(complete)));
+        }
+        if self.dash {
+            manifests.push((format!("{}.mpd", self.name), track.dash_manifest()));
+        }
+        for (name, content) in manifests {
+            std::fs::write(self.output_path.join(&name), content)
+                .map_err(|error| format_err!("Could not write manifest {}: {}", name, error))?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        // A full last sheet was already saved with its last sprite
+        let sprites = self.sprite_index(self.current_image);
+        if !self.individual() && sprites != 0 {
+            self.save_spritesheet(sprites)?;
+        }
+        self.save_bif()?;
+        self.save_hashes()?;
+        self.save_animation()?;
+        self.save_manifests(true)?;
+        self.save_metadata()
+    }
 }
@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+
+use jpeg_encoder::SamplingFactor;
+use media_time::MediaTime;
+
+use crate::options::{Deinterlace, ExtractOptions, FrameInterval, ThumbnailNaming};
+use crate::spritesheet::ImageFormat;
+use crate::tonemap::ToneMapping;
+
+mod animation;
+mod clip;
+mod contactsheet;
+mod cropdetect;
+mod manifest;
+mod naming;
+mod scene;
+mod spritesheet;
+mod tonemap;
+
+/// Options of a plain extraction into 5×5 JPEG spritesheets every 2 seconds, with every
+/// additional output disabled
+pub fn extract_options() -> ExtractOptions {
+    ExtractOptions {
+        max_size: 160,
+        num_horizontal: 5,
+        num_vertical: 5,
+        renditions: Vec::new(),
+        thumbnail_naming: ThumbnailNaming::Index,
+        name_template: None,
+        sheet_template: None,
+        tile_padding: 0,
+        background: image::Rgb([0, 0, 0]),
+        trim_last_sheet: false,
+        base_url: None,
+        frame_interval: FrameInterval::Time(MediaTime::from_seconds(2)),
+        sprite_count: None,
+        format: ImageFormat::Jpeg(75, false, SamplingFactor::R_4_2_0),
+        bif: false,
+        hls: false,
+        dash: false,
+        animation: None,
+        preview_clip: None,
+        contact_sheet: None,
+        perceptual_hash: None,
+        timestamp_overlay: None,
+        max_sheet_size: None,
+        all_frames: false,
+        tone_mapping: ToneMapping::Disabled,
+        color_matrix: None,
+        color_range: None,
+        deinterlace: Deinterlace::Disabled,
+        video_filter: None,
+        crop_detect: false,
+        scene_detection: None,
+        follow: None,
+        resume: false,
+    }
+}
+
+/// Directory for the files of a test, removed with its contents when the test ends
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!(
+            "media-ingestion-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
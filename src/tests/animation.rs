@@ -0,0 +1,74 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Frame, Rgb, RgbImage};
+use media_time::MediaTime;
+
+use crate::animation::{Animation, AnimationEncoder, AnimationFormat, FrameDelay};
+
+const COLORS: [[u8; 3]; 3] = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+
+/// Encodes a solid red, green and blue sprite 2s apart, played back ten times faster
+fn encode(format: AnimationFormat) -> Vec<u8> {
+    let options = Animation {
+        format,
+        frame_delay: FrameDelay::Speed(10.0),
+    };
+    let mut encoder = AnimationEncoder::new(options, Cursor::new(Vec::new()));
+    for (index, color) in COLORS.iter().enumerate() {
+        let image = RgbImage::from_pixel(16, 8, Rgb(*color));
+        encoder
+            .add(MediaTime::from_seconds(2 * index as i64), &image)
+            .unwrap();
+    }
+    encoder
+        .finish(MediaTime::from_seconds(6))
+        .unwrap()
+        .into_inner()
+}
+
+fn check_frames(frames: Vec<Frame>) {
+    assert_eq!(frames.len(), COLORS.len());
+    for (frame, color) in frames.iter().zip(COLORS) {
+        assert_eq!(Duration::from(frame.delay()), Duration::from_millis(200));
+        assert_eq!(frame.buffer().dimensions(), (16, 8));
+        assert_eq!(frame.buffer().get_pixel(8, 4).0[..3], color);
+    }
+}
+
+#[test]
+fn gif_decodes() {
+    let data = encode(AnimationFormat::Gif);
+    let decoder = GifDecoder::new(Cursor::new(data)).unwrap();
+    check_frames(decoder.into_frames().collect_frames().unwrap());
+}
+
+#[test]
+fn webp_decodes() {
+    let data = encode(AnimationFormat::WebP);
+    assert_eq!(&data[4..8], &(data.len() as u32 - 8).to_le_bytes());
+    let decoder = WebPDecoder::new(Cursor::new(data)).unwrap();
+    check_frames(decoder.into_frames().collect_frames().unwrap());
+}
+
+#[test]
+fn apng_decodes() {
+    let data = encode(AnimationFormat::Apng);
+    let decoder = PngDecoder::new(Cursor::new(data)).unwrap();
+    assert!(decoder.is_apng().unwrap());
+    check_frames(decoder.apng().unwrap().into_frames().collect_frames().unwrap());
+}
+
+#[test]
+fn empty_animation_writes_nothing() {
+    let options = Animation {
+        format: AnimationFormat::WebP,
+        frame_delay: FrameDelay::Fixed(MediaTime::from_millis(100)),
+    };
+    let encoder = AnimationEncoder::new(options, Cursor::new(Vec::new()));
+    let data = encoder.finish(MediaTime::from_seconds(6)).unwrap();
+    assert!(data.into_inner().is_empty());
+}
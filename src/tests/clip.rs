@@ -0,0 +1,46 @@
+use ffmpeg_api::api::*;
+use ffmpeg_api::enums::*;
+
+use crate::clip::{ClipEncoder, ClipFormat};
+use crate::tests::TempDir;
+
+#[test]
+fn clip_reads_back() {
+    let dir = TempDir::new("clip");
+    let path = dir.path().join("preview.mp4");
+
+    let mut encoder = ClipEncoder::new(ClipFormat::Mp4, &path, 64, 36).unwrap();
+    let mut frame = AVFrame::new().unwrap();
+    frame.init(64, 36, AVPixelFormat::YUV420P).unwrap();
+    // Plain gray, the planes of a frame share one buffer starting with the luma plane
+    frame.data_mut(0).fill(128);
+    for index in 0..25 {
+        encoder.add_frame(&mut frame, index * 40).unwrap();
+    }
+    encoder.finish().unwrap();
+
+    let mut context = AVFormatContext::new().unwrap();
+    context.open_input(&path).unwrap();
+    let (index, width, height) = {
+        let stream = context
+            .streams()
+            .find(|stream| {
+                stream
+                    .codec_parameters()
+                    .is_ok_and(|parameters| parameters.codec_type() == AVMediaType::Video)
+            })
+            .unwrap();
+        let parameters = stream.codec_parameters().unwrap();
+        (stream.index(), parameters.width(), parameters.height())
+    };
+    assert_eq!((width, height), (64, 36));
+
+    let mut packet = AVPacket::new().unwrap();
+    let mut packets = 0;
+    while context.read_frame(&mut packet).is_ok() {
+        if packet.stream_index() == index {
+            packets += 1;
+        }
+    }
+    assert_eq!(packets, 25);
+}
@@ -0,0 +1,77 @@
+use image::RgbImage;
+use media_time::MediaTime;
+
+use crate::contactsheet::{ContactSheet, ContactSheetBuilder, MediaInfo};
+use crate::spritesheet::ImageFormat;
+
+fn builder(columns: u32, rows: u32, start: Option<i64>) -> ContactSheetBuilder {
+    let options = ContactSheet {
+        columns,
+        rows,
+        format: ImageFormat::Bmp,
+    };
+    let start = start.map(MediaTime::from_millis);
+    ContactSheetBuilder::new(options, MediaTime::from_seconds(10), start)
+}
+
+/// Offers sprites at the given timestamps in milliseconds and returns the kept ones
+fn offer(builder: &mut ContactSheetBuilder, timestamps: &[i64]) -> Vec<i128> {
+    let image = RgbImage::new(300, 100);
+    for &timestamp in timestamps {
+        builder.offer(MediaTime::from_millis(timestamp), &image);
+    }
+    builder.timestamps().iter().map(MediaTime::milliseconds).collect()
+}
+
+fn info() -> MediaInfo {
+    MediaInfo {
+        file_name: String::from("clip.mp4"),
+        file_size: 512,
+        duration: MediaTime::from_seconds(10),
+        width: 1920,
+        height: 1080,
+        codecs: vec![String::from("h264")],
+    }
+}
+
+#[test]
+fn slots_from_stream_start() {
+    // Slots at 1.25s, 3.75s, 6.25s and 8.75s after the start
+    let mut sheet = builder(2, 2, Some(1400));
+    let sprites = [1400, 3400, 5400, 7400, 9400, 11400];
+    assert_eq!(offer(&mut sheet, &sprites), [3400, 5400, 9400, 11400]);
+}
+
+#[test]
+fn slots_from_first_sprite() {
+    let mut sheet = builder(2, 2, None);
+    let sprites = [1400, 3400, 5400, 7400, 9400, 11400];
+    assert_eq!(offer(&mut sheet, &sprites), [3400, 5400, 9400, 11400]);
+}
+
+#[test]
+fn sparse_sprites_fill_several_slots() {
+    let mut sheet = builder(2, 2, Some(0));
+    assert_eq!(offer(&mut sheet, &[0, 5000, 10000]), [5000, 10000]);
+}
+
+#[test]
+fn render_without_frames() {
+    // Only the header, whose longest line is "Resolution: 1920x1080"
+    let sheet = builder(3, 2, Some(0)).render(&info());
+    assert_eq!(sheet.dimensions(), (282, 140));
+}
+
+#[test]
+fn render_single_frame() {
+    let mut sheet = builder(3, 2, Some(0));
+    offer(&mut sheet, &[2000]);
+    assert_eq!(sheet.render(&info()).dimensions(), (332, 256));
+}
+
+#[test]
+fn render_partial_row() {
+    let mut sheet = builder(3, 2, Some(0));
+    offer(&mut sheet, &[2000, 4000, 6000, 8000]);
+    assert_eq!(sheet.render(&info()).dimensions(), (948, 380));
+}
@@ -0,0 +1,75 @@
+use crate::cropdetect::{CropDetector, CropRect};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+/// Rows are padded like FFmpeg's aligned planes, with bright bytes that must not be analyzed
+const STRIDE: usize = 72;
+
+/// GRAY8 frame that is black except for the picture between the given left, top, right and
+/// bottom edges
+fn frame(picture: (u32, u32, u32, u32)) -> Vec<u8> {
+    let (left, top, right, bottom) = picture;
+    let mut luma = vec![255; STRIDE * HEIGHT as usize];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let inside = (left..right).contains(&x) && (top..bottom).contains(&y);
+            luma[y as usize * STRIDE + x as usize] = if inside { 128 } else { 16 };
+        }
+    }
+    luma
+}
+
+fn detect(frames: &[Vec<u8>]) -> Option<CropRect> {
+    let mut detector = CropDetector::new(WIDTH, HEIGHT);
+    for luma in frames {
+        detector.analyze(luma, STRIDE);
+    }
+    detector.result()
+}
+
+#[test]
+fn letterbox() {
+    let crop = detect(&[frame((0, 6, 64, 42))]);
+    let expected = CropRect {
+        x: 0,
+        y: 6,
+        width: 64,
+        height: 36,
+    };
+    assert_eq!(crop, Some(expected));
+    assert_eq!(expected.to_string(), "64:36:0:6");
+}
+
+#[test]
+fn full_frame() {
+    assert_eq!(detect(&[frame((0, 0, 64, 48))]), None);
+}
+
+#[test]
+fn black_frames_are_ignored() {
+    let black = frame((0, 0, 0, 0));
+    assert_eq!(detect(std::slice::from_ref(&black)), None);
+    let crop = detect(&[black.clone(), frame((0, 6, 64, 42)), black]);
+    assert_eq!(crop.map(|crop| (crop.y, crop.height)), Some((6, 36)));
+}
+
+#[test]
+fn odd_bars_keep_the_picture() {
+    // Odd edges are widened to even coordinates rather than cutting into the picture
+    let crop = detect(&[frame((3, 5, 64, 41))]);
+    let expected = CropRect {
+        x: 2,
+        y: 4,
+        width: 62,
+        height: 38,
+    };
+    assert_eq!(crop, Some(expected));
+}
+
+#[test]
+fn union_of_frames() {
+    let crop = detect(&[frame((0, 6, 64, 42)), frame((8, 0, 56, 48))]);
+    assert_eq!(crop, None);
+    let crop = detect(&[frame((0, 10, 64, 38)), frame((0, 6, 64, 30))]);
+    assert_eq!(crop.map(|crop| (crop.y, crop.height)), Some((6, 32)));
+}
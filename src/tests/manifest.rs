@@ -0,0 +1,86 @@
+use media_time::MediaTime;
+
+use crate::manifest::{ThumbnailTrack, TileSheet};
+
+/// Two full 5×5 sheets of sprites every 2s and a trimmed last sheet of three sprites
+fn track() -> ThumbnailTrack {
+    let sheet = |index: u32, start: i64, tiles: u32, columns: u32, rows: u32| TileSheet {
+        uri: format!("preview_{}.jpeg", index),
+        start: MediaTime::from_millis(start),
+        duration: MediaTime::from_millis(tiles as i64 * 2000),
+        tiles,
+        columns,
+        rows,
+        size: tiles as u64 * 400,
+    };
+    ThumbnailTrack {
+        media: String::from("preview_$Number$.jpeg"),
+        mime_type: "image/jpeg",
+        codec: "jpeg",
+        columns: 5,
+        rows: 5,
+        tile_width: 160,
+        tile_height: 90,
+        sheets: vec![
+            sheet(0, 0, 25, 5, 5),
+            sheet(1, 50000, 25, 5, 5),
+            sheet(2, 100000, 3, 3, 1),
+        ],
+    }
+}
+
+#[test]
+fn hls_tiles() {
+    let playlist = track().hls_media_playlist(true);
+    let tiles: Vec<&str> = playlist
+        .lines()
+        .filter(|line| line.starts_with("#EXT-X-TILES:"))
+        .collect();
+    assert_eq!(
+        tiles,
+        [
+            "#EXT-X-TILES:RESOLUTION=160x90,LAYOUT=5x5,DURATION=2.000",
+            "#EXT-X-TILES:RESOLUTION=160x90,LAYOUT=5x5,DURATION=2.000",
+            "#EXT-X-TILES:RESOLUTION=160x90,LAYOUT=3x1,DURATION=2.000",
+        ]
+    );
+    assert!(playlist.contains("#EXT-X-TARGETDURATION:50\n"));
+    assert!(playlist.contains("#EXTINF:6.000,\n#EXT-X-TILES:"));
+    assert!(playlist.ends_with("preview_2.jpeg\n#EXT-X-ENDLIST\n"));
+}
+
+#[test]
+fn hls_event_playlist() {
+    let playlist = track().hls_media_playlist(false);
+    assert!(playlist.contains("#EXT-X-PLAYLIST-TYPE:EVENT\n"));
+    assert!(!playlist.contains("#EXT-X-ENDLIST"));
+}
+
+#[test]
+fn dash_segment_timeline() {
+    let adaptation_set = track().dash_adaptation_set();
+    let (_, timeline) = adaptation_set.split_once("<SegmentTimeline>\n").unwrap();
+    let (timeline, _) = timeline.split_once("        </SegmentTimeline>").unwrap();
+    assert_eq!(
+        timeline,
+        concat!(
+            "          <S t=\"0\" d=\"50000\" r=\"1\"/>\n",
+            "          <S t=\"100000\" d=\"6000\"/>\n",
+        )
+    );
+    assert!(adaptation_set.contains("width=\"800\" height=\"450\""));
+    assert!(adaptation_set.contains("value=\"5x5\""));
+}
+
+#[test]
+fn dash_duration() {
+    assert!(track().dash_manifest().contains("mediaPresentationDuration=\"PT106.000S\""));
+}
+
+#[test]
+fn dash_escapes_media() {
+    let mut track = track();
+    track.media = String::from("a&b/<\"sheet\">_$Number$.jpeg");
+    let adaptation_set = track.dash_adaptation_set();
+    assert!(adaptation_set.contains("media=\"a&amp;b/&lt;&quot;sheet&quot;&gt;_$Number$.jpeg\""));
+}
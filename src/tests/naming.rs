@@ -0,0 +1,44 @@
+use crate::naming::{padding, render_template};
+
+/// Values of a sheet with index 7 starting at 12.5s in the 320 rendition
+fn sheet_value(key: &str, spec: Option<&str>) -> Option<String> {
+    match key {
+        "index" => Some(format!("{:0width$}", 7, width = padding(spec))),
+        "timestamp" => Some(format!("{:0width$}", 12500, width = padding(spec))),
+        "rendition" => Some(String::from("320")),
+        _ => None,
+    }
+}
+
+#[test]
+fn index_padding() {
+    assert_eq!(render_template("sheet-{index:04}.jpg", sheet_value), "sheet-0007.jpg");
+    assert_eq!(render_template("sheet-{index}.jpg", sheet_value), "sheet-7.jpg");
+    assert_eq!(render_template("sheet-{index:1}.jpg", sheet_value), "sheet-7.jpg");
+}
+
+#[test]
+fn rendition() {
+    assert_eq!(render_template("preview-{rendition}", sheet_value), "preview-320");
+    assert_eq!(render_template("{rendition}/{rendition}", sheet_value), "320/320");
+}
+
+#[test]
+fn timestamp() {
+    assert_eq!(render_template("{timestamp}.jpg", sheet_value), "12500.jpg");
+    assert_eq!(render_template("{timestamp:08}.jpg", sheet_value), "00012500.jpg");
+}
+
+#[test]
+fn stages() {
+    let template = render_template("{stem}-{rendition}-{index:04}.{ext}", |key, _| {
+        (key == "stem").then(|| String::from("movie"))
+    });
+    assert_eq!(template, "movie-{rendition}-{index:04}.{ext}");
+    assert_eq!(render_template(&template, sheet_value), "movie-320-0007.{ext}");
+}
+
+#[test]
+fn unclosed_placeholder() {
+    assert_eq!(render_template("{index}-{index", sheet_value), "7-{index");
+}
@@ -0,0 +1,65 @@
+use image::{Rgb, RgbImage};
+use media_time::MediaTime;
+
+use super::{TempDir, extract_options};
+use crate::scene::{SceneDetection, SceneDetector};
+use crate::spritesheet::SpritesheetManager;
+
+fn options() -> SceneDetection {
+    SceneDetection {
+        threshold: 0.3,
+        min_interval: MediaTime::from_millis(300),
+        max_interval: MediaTime::from_seconds(5),
+    }
+}
+
+fn image(value: u8) -> RgbImage {
+    RgbImage::from_pixel(160, 90, Rgb([value, value, value]))
+}
+
+#[test]
+fn identical_frames() {
+    let mut detector = SceneDetector::new(options());
+    // Without an accepted sprite every frame starts a scene
+    assert!(detector.is_scene_change(&image(100)));
+    detector.accept(&image(100));
+    assert!(!detector.is_scene_change(&image(100)));
+    assert!(!detector.is_scene_change(&image(120)));
+}
+
+#[test]
+fn hard_cut() {
+    let mut detector = SceneDetector::new(options());
+    detector.accept(&image(20));
+    assert!(detector.is_scene_change(&image(230)));
+
+    // Half of the frame cut to white exceeds the threshold as well
+    let mut half = image(20);
+    for (_, _, pixel) in half.enumerate_pixels_mut().filter(|(x, _, _)| *x >= 80) {
+        *pixel = Rgb([255, 255, 255]);
+    }
+    assert!(detector.is_scene_change(&half));
+}
+
+#[test]
+fn forced_sprite_at_max_interval() {
+    let dir = TempDir::new("scene-max-interval");
+    let mut options = extract_options();
+    options.scene_detection = Some(self::options());
+    let mut manager = SpritesheetManager::new(options, dir.path(), "preview");
+    manager.initialize(1920, 1080);
+
+    // A single cut at 2.5s, otherwise a still image for 10 seconds
+    let mut sprites = Vec::new();
+    for frame in 0..=20 {
+        let timestamp = MediaTime::from_millis(frame * 500);
+        let image = image(if frame < 5 { 20 } else { 230 });
+        if manager.fulfils_frame_interval(timestamp)
+            && manager.fulfils_scene_change(timestamp, &image)
+        {
+            manager.add_image(timestamp, image).unwrap();
+            sprites.push(timestamp.milliseconds());
+        }
+    }
+    assert_eq!(sprites, [0, 2500, 7500]);
+}
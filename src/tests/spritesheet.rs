@@ -0,0 +1,248 @@
+use image::codecs::png::{CompressionType, FilterType};
+use image::{Rgb, RgbImage};
+use jpeg_encoder::SamplingFactor;
+use media_time::MediaTime;
+use webvtt::WebVTTFile;
+
+use super::{TempDir, extract_options};
+use crate::options::{ExtractOptions, FrameInterval};
+use crate::spritesheet::{ImageFormat, SpritesheetManager};
+
+/// Feeds frames every 100ms from `first` to `first + 10s` and returns the timestamps in
+/// milliseconds of the frames chosen as sprites
+fn spread_sprites(name: &str, start_time: Option<i64>, first: i64) -> Vec<i128> {
+    let dir = TempDir::new(name);
+    let mut options = extract_options();
+    options.sprite_count = Some(4);
+    options.frame_interval = FrameInterval::Time(MediaTime::from_seconds(10) / 4);
+    let mut manager = SpritesheetManager::new(options, dir.path(), "preview");
+    manager.set_start_time(start_time.map(MediaTime::from_millis));
+    manager.initialize(1920, 1080);
+
+    let mut sprites = Vec::new();
+    for frame in 0..100 {
+        let timestamp = MediaTime::from_millis(first + frame * 100);
+        if manager.fulfils_frame_interval(timestamp) {
+            let image = RgbImage::new(manager.sprite_width(), manager.sprite_height());
+            manager.add_image(timestamp, image).unwrap();
+            sprites.push(timestamp.milliseconds());
+        }
+    }
+    sprites
+}
+
+#[test]
+fn sprite_count_from_zero() {
+    assert_eq!(spread_sprites("count-zero", Some(0), 0), [0, 2500, 5000, 7500]);
+}
+
+#[test]
+fn sprite_count_from_stream_start() {
+    // MPEG-TS streams commonly start at a few seconds
+    assert_eq!(spread_sprites("count-start", Some(1400), 1400), [1400, 3900, 6400, 8900]);
+    // A late first keyframe doesn't shift the following sprites
+    assert_eq!(spread_sprites("count-late", Some(1000), 1400), [1400, 3500, 6000, 8500]);
+}
+
+#[test]
+fn sprite_count_from_first_frame() {
+    assert_eq!(spread_sprites("count-first", None, 1400), [1400, 3900, 6400, 8900]);
+}
+
+/// Pseudo random noise, which JPEG can't compress well
+fn noise() -> RgbImage {
+    let mut state = 0x2545_f491_u32;
+    RgbImage::from_fn(160, 90, |_, _| {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let [r, g, b, _] = state.to_le_bytes();
+        Rgb([r, g, b])
+    })
+}
+
+fn jpeg_size(quality: u8) -> u64 {
+    let mut data = Vec::new();
+    ImageFormat::Jpeg(quality, false, SamplingFactor::R_4_2_0)
+        .encode(&noise(), &mut data)
+        .unwrap();
+    data.len() as u64
+}
+
+/// Encodes the noise as a sheet with the given size budget and returns the size of the encoded
+/// sheet and the notes of the metadata
+fn encode_with_budget(name: &str, max_sheet_size: u64) -> (u64, Vec<String>) {
+    let dir = TempDir::new(name);
+    let mut options = extract_options();
+    options.max_sheet_size = Some(max_sheet_size);
+    let mut manager = SpritesheetManager::new(options, dir.path(), "preview");
+    let data = manager.encode_spritesheet("preview_0.jpeg", &noise()).unwrap();
+    (data.len() as u64, manager.metadata().notes().to_vec())
+}
+
+#[test]
+fn size_budget_met_at_configured_quality() {
+    let (size, notes) = encode_with_budget("budget-met", jpeg_size(75));
+    assert_eq!(size, jpeg_size(75));
+    assert!(notes.is_empty());
+}
+
+#[test]
+fn size_budget_lowers_quality() {
+    let budget = jpeg_size(40);
+    let (size, notes) = encode_with_budget("budget-lower", budget);
+    assert!(size <= budget);
+    assert_eq!(notes, [format!("preview_0.jpeg quality=40 size={}", size)]);
+}
+
+#[test]
+fn size_budget_impossible() {
+    let (size, notes) = encode_with_budget("budget-impossible", 100);
+    assert_eq!(size, jpeg_size(1));
+    assert_eq!(notes, [format!("preview_0.jpeg quality=1 size={}", size)]);
+}
+
+/// Extracts white sprites every 2 seconds into lossless sheets of three columns and the given
+/// rows and returns the metadata
+fn extract(dir: &TempDir, mut options: ExtractOptions, rows: u32, sprites: i64) -> WebVTTFile {
+    options.num_horizontal = 3;
+    options.num_vertical = rows;
+    options.format = ImageFormat::Png(CompressionType::Fast, FilterType::NoFilter);
+    let mut manager = SpritesheetManager::new(options, dir.path(), "preview");
+    manager.initialize(1920, 1080);
+    for sprite in 0..sprites {
+        let image = RgbImage::from_pixel(160, 90, Rgb([255, 255, 255]));
+        manager.add_image(MediaTime::from_seconds(sprite * 2), image).unwrap();
+    }
+    manager.end_frame(MediaTime::from_seconds(sprites * 2));
+    manager.save().unwrap();
+    WebVTTFile::load(dir.path().join("preview.vtt")).unwrap()
+}
+
+fn payloads(metadata: &WebVTTFile) -> Vec<&str> {
+    metadata.cues().iter().map(|cue| cue.payload()).collect()
+}
+
+fn sheet(dir: &TempDir, index: u32) -> RgbImage {
+    image::open(dir.path().join(format!("preview_{}.png", index)))
+        .unwrap()
+        .to_rgb8()
+}
+
+#[test]
+fn padded_tiles() {
+    let dir = TempDir::new("padded-tiles");
+    let mut options = extract_options();
+    options.tile_padding = 2;
+    options.background = Rgb([255, 0, 255]);
+    let metadata = extract(&dir, options, 2, 7);
+    assert_eq!(
+        payloads(&metadata),
+        [
+            "preview_0.png#xywh=2,2,160,90",
+            "preview_0.png#xywh=166,2,160,90",
+            "preview_0.png#xywh=330,2,160,90",
+            "preview_0.png#xywh=2,96,160,90",
+            "preview_0.png#xywh=166,96,160,90",
+            "preview_0.png#xywh=330,96,160,90",
+            "preview_1.png#xywh=2,2,160,90",
+        ]
+    );
+
+    let first = sheet(&dir, 0);
+    assert_eq!(first.dimensions(), (492, 188));
+    // Padding around the sprites is filled with the background
+    assert_eq!(*first.get_pixel(1, 1), Rgb([255, 0, 255]));
+    assert_eq!(*first.get_pixel(2, 2), Rgb([255, 255, 255]));
+    assert_eq!(*first.get_pixel(161, 91), Rgb([255, 255, 255]));
+    assert_eq!(*first.get_pixel(163, 93), Rgb([255, 0, 255]));
+    // Unused cells of the last sheet as well
+    assert_eq!(*sheet(&dir, 1).get_pixel(250, 140), Rgb([255, 0, 255]));
+}
+
+#[test]
+fn trimmed_last_sheet() {
+    let dir = TempDir::new("trimmed-rows");
+    let mut options = extract_options();
+    options.trim_last_sheet = true;
+    options.tile_padding = 2;
+    let metadata = extract(&dir, options, 3, 13);
+    assert_eq!(sheet(&dir, 0).dimensions(), (492, 282));
+    // Four sprites fill a row and a half, so only the unused third row is cut
+    assert_eq!(sheet(&dir, 1).dimensions(), (492, 188));
+    assert_eq!(
+        payloads(&metadata)[9..],
+        [
+            "preview_1.png#xywh=2,2,160,90",
+            "preview_1.png#xywh=166,2,160,90",
+            "preview_1.png#xywh=330,2,160,90",
+            "preview_1.png#xywh=2,96,160,90",
+        ]
+    );
+
+    let dir = TempDir::new("trimmed-columns");
+    let mut options = extract_options();
+    options.trim_last_sheet = true;
+    options.tile_padding = 2;
+    let metadata = extract(&dir, options, 2, 8);
+    // A single row is cut to the used columns, the cues keep their coordinates
+    assert_eq!(sheet(&dir, 1).dimensions(), (328, 94));
+    assert_eq!(
+        payloads(&metadata)[6..],
+        ["preview_1.png#xywh=2,2,160,90", "preview_1.png#xywh=166,2,160,90"]
+    );
+
+    let dir = TempDir::new("untrimmed");
+    extract(&dir, extract_options(), 2, 8);
+    assert_eq!(sheet(&dir, 1).dimensions(), (480, 180));
+}
+
+/// Adds the given range of white sprites, one every 2 seconds
+fn add_sprites(manager: &mut SpritesheetManager, sprites: std::ops::Range<i64>) {
+    for sprite in sprites {
+        let image = RgbImage::from_pixel(160, 90, Rgb([255, 255, 255]));
+        manager.add_image(MediaTime::from_seconds(sprite * 2), image).unwrap();
+    }
+}
+
+#[test]
+fn resume_after_last_complete_sheet() {
+    let dir = TempDir::new("resume");
+    let mut options = extract_options();
+    options.num_horizontal = 3;
+    options.num_vertical = 2;
+    options.max_sheet_size = Some(1);
+    options.resume = true;
+
+    // A previous run ended in the middle of the second sheet
+    let mut manager = SpritesheetManager::new(options.clone(), dir.path(), "preview");
+    manager.initialize(1920, 1080);
+    add_sprites(&mut manager, 0..8);
+    manager.add_note("duration unknown");
+    manager.end_frame(MediaTime::from_seconds(16));
+    manager.save().unwrap();
+
+    let mut manager = SpritesheetManager::new(options, dir.path(), "preview");
+    assert_eq!(manager.resume().unwrap(), Some(MediaTime::from_seconds(10)));
+    // The cue of the last sprite of the first sheet ends with the next sprite
+    assert_eq!(manager.metadata().cues().len(), 5);
+    // The size note of the incomplete second sheet is dropped, it is encoded again
+    let notes = manager.metadata().notes();
+    assert_eq!(notes.len(), 2);
+    assert!(notes[0].starts_with("preview_0.jpeg quality=1 "));
+    assert_eq!(notes[1], "duration unknown");
+
+    // Continuing at the seventh sprite starts the second sheet again
+    manager.initialize(1920, 1080);
+    add_sprites(&mut manager, 6..7);
+    manager.end_frame(MediaTime::from_seconds(14));
+    manager.save().unwrap();
+    let metadata = WebVTTFile::load(dir.path().join("preview.vtt")).unwrap();
+    assert_eq!(
+        payloads(&metadata)[5..],
+        [
+            "preview_0.jpeg#xywh=320,90,160,90",
+            "preview_1.jpeg#xywh=0,0,160,90",
+        ]
+    );
+    assert_eq!(metadata.cues()[5].end(), MediaTime::from_seconds(12));
+    assert_eq!(metadata.notes().len(), 3);
+}
@@ -0,0 +1,98 @@
+use ffmpeg_api::enums::{AVColorPrimaries, AVColorTransferCharacteristic};
+
+use crate::tonemap::{ToneMapper, ToneMapping, hlg_inverse_oetf, pq_eotf, pq_inverse_eotf};
+
+fn pq_mapper(mode: ToneMapping, primaries: AVColorPrimaries) -> ToneMapper {
+    ToneMapper::new(mode, AVColorTransferCharacteristic::SMPTE2084, primaries).unwrap()
+}
+
+/// 16-bit PQ code value of the given luminance in nits
+fn pq_code(nits: f32) -> u16 {
+    (pq_inverse_eotf(nits) * u16::MAX as f32).round() as u16
+}
+
+/// Tone maps a single RGB48LE pixel
+fn map_pixel(mapper: &ToneMapper, rgb: [u16; 3]) -> [u8; 3] {
+    let data: Vec<u8> = rgb.iter().flat_map(|channel| channel.to_le_bytes()).collect();
+    mapper.apply(&data, 1, 1).get_pixel(0, 0).0
+}
+
+#[test]
+fn pq_transfer() {
+    assert!((pq_eotf(1.0) - 10000.0).abs() < 1.0);
+    assert_eq!(pq_eotf(0.0), 0.0);
+    assert!((pq_inverse_eotf(10000.0) - 1.0).abs() < 1e-4);
+    assert!((pq_eotf(pq_inverse_eotf(100.0)) - 100.0).abs() < 0.1);
+}
+
+#[test]
+fn hlg_reference_white() {
+    assert!((hlg_inverse_oetf(0.5) - 1.0 / 12.0).abs() < 1e-6);
+    assert!((hlg_inverse_oetf(1.0) - 1.0).abs() < 1e-3);
+    // 75% HLG is reference white at 203 nits on a 1000 nit display
+    let nits = hlg_inverse_oetf(0.75).powf(1.2) * 1000.0;
+    assert!((nits - 203.0).abs() < 1.0, "{}", nits);
+}
+
+#[test]
+fn curves_are_monotonic() {
+    for mode in [ToneMapping::Reinhard, ToneMapping::Hable, ToneMapping::Bt2390] {
+        let mapper = pq_mapper(mode, AVColorPrimaries::BT709);
+        let mut previous = 0.0;
+        // Up to the assumed mastering peak of 10 times SDR reference white
+        for step in 1..=1000 {
+            let value = mapper.curve(step as f32 / 100.0);
+            assert!(value >= previous, "{:?} decreases at {}", mode, step);
+            previous = value;
+        }
+        assert!((previous - 1.0).abs() < 1e-3, "{:?} maps the peak to {}", mode, previous);
+    }
+}
+
+#[test]
+fn lookup_table() {
+    let mapper = pq_mapper(ToneMapping::Clip, AVColorPrimaries::BT709);
+    assert_eq!(map_pixel(&mapper, [0, 0, 0]), [0, 0, 0]);
+    assert_eq!(map_pixel(&mapper, [pq_code(100.0); 3]), [255, 255, 255]);
+    assert_eq!(map_pixel(&mapper, [u16::MAX; 3]), [255, 255, 255]);
+    // Half of SDR reference white is 188 in sRGB
+    let gray = map_pixel(&mapper, [pq_code(50.0); 3]);
+    assert!(gray.iter().all(|channel| channel.abs_diff(188) <= 1), "{:?}", gray);
+}
+
+#[test]
+fn gamut_mapping() {
+    let bt709 = pq_mapper(ToneMapping::Clip, AVColorPrimaries::BT709);
+    let bt2020 = pq_mapper(ToneMapping::Clip, AVColorPrimaries::BT2020);
+
+    // Neutral colors keep their level
+    let white = [pq_code(100.0); 3];
+    assert_eq!(map_pixel(&bt2020, white), map_pixel(&bt709, white));
+
+    // BT.2020 green is more saturated than any BT.709 color, so red and blue are clipped away
+    let green = [0, pq_code(50.0), 0];
+    let [red, _, blue] = map_pixel(&bt2020, green);
+    assert_eq!((red, blue), (0, 0));
+
+    // Desaturated colors become more saturated in the narrower gamut
+    let mixed = [pq_code(20.0), pq_code(50.0), pq_code(20.0)];
+    let [wide_red, wide_green, _] = map_pixel(&bt709, mixed);
+    let [red, green, _] = map_pixel(&bt2020, mixed);
+    assert!(red < wide_red && green > wide_green);
+}
+
+#[test]
+fn bt709_passthrough() {
+    let sdr = ToneMapper::new(
+        ToneMapping::Hable,
+        AVColorTransferCharacteristic::BT709,
+        AVColorPrimaries::BT709,
+    );
+    assert!(sdr.is_none());
+    let disabled = ToneMapper::new(
+        ToneMapping::Disabled,
+        AVColorTransferCharacteristic::SMPTE2084,
+        AVColorPrimaries::BT2020,
+    );
+    assert!(disabled.is_none());
+}
@@ -0,0 +1,165 @@
+use ffmpeg_api::enums::{AVColorPrimaries, AVColorTransferCharacteristic};
+use image::RgbImage;
+
+/// Luminance of SDR reference white, in nits
+const SDR_WHITE: f32 = 100.0;
+/// Assumed mastering peak of HDR sources, in nits
+const HDR_PEAK: f32 = 1000.0;
+
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+const HLG_A: f32 = 0.17883277;
+const HLG_B: f32 = 0.28466892;
+const HLG_C: f32 = 0.5599107;
+
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.6605, -0.5876, -0.0728],
+    [-0.1246, 1.1329, -0.0083],
+    [-0.0182, -0.1006, 1.1187],
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMapping {
+    Disabled,
+    Clip,
+    Reinhard,
+    Hable,
+    Bt2390,
+}
+
+pub struct ToneMapper {
+    mode: ToneMapping,
+    gamut_mapping: bool,
+    linear: Vec<f32>,
+}
+
+pub(crate) fn pq_eotf(value: f32) -> f32 {
+    let e = value.max(0.0).powf(1.0 / PQ_M2);
+    (((e - PQ_C1).max(0.0)) / (PQ_C2 - PQ_C3 * e)).powf(1.0 / PQ_M1) * 10000.0
+}
+
+pub(crate) fn pq_inverse_eotf(nits: f32) -> f32 {
+    let y = (nits / 10000.0).max(0.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+}
+
+pub(crate) fn hlg_inverse_oetf(value: f32) -> f32 {
+    if value <= 0.5 {
+        value * value / 3.0
+    } else {
+        (((value - HLG_C) / HLG_A).exp() + HLG_B) / 12.0
+    }
+}
+
+fn hable(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+fn bt2390(signal: f32) -> f32 {
+    let source_peak = pq_inverse_eotf(HDR_PEAK);
+    let max_lum = pq_inverse_eotf(SDR_WHITE) / source_peak;
+    let knee = 1.5 * max_lum - 0.5;
+
+    let e1 = (pq_inverse_eotf(signal * SDR_WHITE) / source_peak).min(1.0);
+    let e2 = if e1 < knee {
+        e1
+    } else {
+        let t = (e1 - knee) / (1.0 - knee);
+        let t2 = t * t;
+        let t3 = t2 * t;
+        (2.0 * t3 - 3.0 * t2 + 1.0) * knee
+            + (t3 - 2.0 * t2 + t) * (1.0 - knee)
+            + (-2.0 * t3 + 3.0 * t2) * max_lum
+    };
+
+    pq_eotf(e2 * source_peak) / SDR_WHITE
+}
+
+fn srgb_oetf(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl ToneMapper {
+    /// Returns a tone mapper if the given transfer characteristic is HDR and tone mapping is enabled
+    pub fn new(
+        mode: ToneMapping,
+        transfer: AVColorTransferCharacteristic,
+        primaries: AVColorPrimaries,
+    ) -> Option<ToneMapper> {
+        if mode == ToneMapping::Disabled || !transfer.is_hdr() {
+            return None;
+        }
+
+        // Linearizes 16-bit code values into display light relative to SDR reference white
+        let linear = (0..=u16::MAX)
+            .map(|code| {
+                let value = code as f32 / u16::MAX as f32;
+                match transfer {
+                    AVColorTransferCharacteristic::SMPTE2084 => pq_eotf(value) / SDR_WHITE,
+                    // HLG system gamma is applied per channel as an approximation of the OOTF
+                    _ => hlg_inverse_oetf(value).powf(1.2) * HDR_PEAK / SDR_WHITE,
+                }
+            })
+            .collect();
+
+        Some(ToneMapper {
+            mode,
+            gamut_mapping: primaries != AVColorPrimaries::BT709,
+            linear,
+        })
+    }
+
+    pub(crate) fn curve(&self, signal: f32) -> f32 {
+        let peak = HDR_PEAK / SDR_WHITE;
+        match self.mode {
+            ToneMapping::Disabled | ToneMapping::Clip => signal.min(1.0),
+            ToneMapping::Reinhard => signal / (signal + 1.0) * (peak + 1.0) / peak,
+            ToneMapping::Hable => hable(signal) / hable(peak),
+            ToneMapping::Bt2390 => bt2390(signal),
+        }
+    }
+
+    /// Maps packed RGB48LE data into an 8-bit sRGB image
+    pub fn apply(&self, data: &[u8], width: u32, height: u32) -> RgbImage {
+        let mut image = RgbImage::new(width, height);
+        for (pixel, source) in image.pixels_mut().zip(data.chunks_exact(6)) {
+            let mut rgb = [0.0f32; 3];
+            for (channel, bytes) in rgb.iter_mut().zip(source.chunks_exact(2)) {
+                *channel = self.linear[u16::from_le_bytes([bytes[0], bytes[1]]) as usize];
+            }
+
+            // Tone map the brightest channel and scale the others along to preserve hue
+            let signal = rgb[0].max(rgb[1]).max(rgb[2]);
+            if signal > 0.0 {
+                let scale = self.curve(signal) / signal;
+                rgb.iter_mut().for_each(|channel| *channel *= scale);
+            }
+
+            if self.gamut_mapping {
+                let [r, g, b] = rgb;
+                for (channel, row) in rgb.iter_mut().zip(BT2020_TO_BT709.iter()) {
+                    *channel = row[0] * r + row[1] * g + row[2] * b;
+                }
+            }
+
+            for (target, channel) in pixel.0.iter_mut().zip(rgb.iter()) {
+                *target = (srgb_oetf(channel.clamp(0.0, 1.0)) * 255.0).round() as u8;
+            }
+        }
+        image
+    }
+}